@@ -0,0 +1,122 @@
+use std::{collections::HashSet, env, fs, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use database::ConnectionOptions;
+use publisher::{template::DEFAULT_TEMPLATE, PublisherConfig, TwitterConfig};
+
+use crate::{schedule::ScheduleConfig, selection::SelectionConfig};
+
+const DEFAULT_CONFIG_FILE: &str = "noi.toml";
+
+fn default_required() -> bool {
+    true
+}
+
+/// A destination tracks are posted to
+#[derive(Debug, Clone, Deserialize)]
+pub struct Destination {
+    /// Identifies the destination in the history of posts. Defaults to its kind
+    /// e.g. `mastodon`, so it only needs to be set when there are several
+    /// destinations of the same kind
+    pub name: Option<String>,
+    /// A track is only marked as posted once every required destination succeeds
+    #[serde(default = "default_required")]
+    pub required: bool,
+    /// Overrides the template of posts sent to this destination
+    pub template: Option<String>,
+    #[serde(flatten)]
+    pub publisher: PublisherConfig,
+}
+
+impl Destination {
+    /// The name posts to this destination are recorded under
+    pub fn name(&self) -> &str {
+        self.name
+            .as_deref()
+            .unwrap_or_else(|| self.publisher.name())
+    }
+}
+
+/// Settings read from the config file. The file is optional, every setting
+/// has a default that matches how Noiisseur behaves without one
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Where tracks are posted to. Defaults to Twitter when empty
+    pub destinations: Vec<Destination>,
+    /// The template posts are rendered from. Defaults to posting just the link
+    pub template: Option<String>,
+    /// How connections to the database are set up
+    pub database: ConnectionOptions,
+    /// How the track to post is picked
+    pub selection: SelectionConfig,
+    /// When `noi run` updates tracks and posts
+    pub schedule: ScheduleConfig,
+}
+
+impl Config {
+    /// Loads the config from the file in `NOI_CONFIG`, falling back to `noi.toml`
+    pub fn load() -> Result<Config> {
+        let path = env::var("NOI_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_CONFIG_FILE));
+
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+        // Posts are matched back to their destination by name to undo them
+        let mut names = HashSet::new();
+        for destination in config.destinations.iter() {
+            if !names.insert(destination.name()) {
+                bail!(
+                    "Invalid config file {}: there are several destinations named {}, \
+                    give each of them a unique `name`",
+                    path.display(),
+                    destination.name()
+                );
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn destinations(&self) -> Vec<Destination> {
+        if self.destinations.is_empty() {
+            vec![Destination {
+                name: None,
+                required: true,
+                template: None,
+                publisher: PublisherConfig::Twitter(TwitterConfig::default()),
+            }]
+        } else {
+            self.destinations.clone()
+        }
+    }
+
+    /// The template used for posts sent to a destination
+    pub fn template<'a>(&'a self, destination: &'a Destination) -> &'a str {
+        destination
+            .template
+            .as_deref()
+            .or(self.template.as_deref())
+            .unwrap_or(DEFAULT_TEMPLATE)
+    }
+
+    /// The config of the first Twitter destination
+    pub fn twitter(&self) -> TwitterConfig {
+        self.destinations()
+            .into_iter()
+            .find_map(|destination| match destination.publisher {
+                PublisherConfig::Twitter(config) => Some(config),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
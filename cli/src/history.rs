@@ -0,0 +1,177 @@
+use std::{io, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use database::models::{Playlist, Post, Track};
+
+use crate::table;
+
+/// How `noi history` prints posts
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(anyhow!("Unknown format {}, expected table, json or csv", s)),
+        }
+    }
+}
+
+/// Parses either a date (`2021-04-01`) or a date and time (`2021-04-01 09:00:00`)
+pub fn parse_since(s: &str) -> Result<NaiveDateTime> {
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Ok(datetime);
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| anyhow!("Invalid date {}, expected YYYY-MM-DD", s))?;
+    date.and_hms_opt(0, 0, 0)
+        .ok_or_else(|| anyhow!("Invalid date {}, expected YYYY-MM-DD", s))
+}
+
+#[derive(Serialize)]
+struct HistoryRow {
+    id: i32,
+    posted_at: String,
+    track: String,
+    playlist: String,
+    destination: String,
+    status: String,
+    url: String,
+    /// The strategy the track was picked with. Empty for posts made before
+    /// strategies were recorded
+    strategy: String,
+}
+
+impl HistoryRow {
+    fn new(post: Post, track: Track, playlists: Vec<Playlist>) -> Self {
+        HistoryRow {
+            id: post.id,
+            // Unknown for posts made before posts were recorded
+            posted_at: match post.posted_at {
+                Some(posted_at) => posted_at.format("%Y-%m-%d %H:%M").to_string(),
+                None => "unknown".to_string(),
+            },
+            track: track.name,
+            // Empty once the track is no longer in any playlist
+            playlist: playlists
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+                .join(", "),
+            destination: post.destination,
+            status: post.status,
+            url: post.remote_url.unwrap_or_default(),
+            strategy: post.strategy.unwrap_or_default(),
+        }
+    }
+}
+
+pub fn print(history: Vec<(Post, Track, Vec<Playlist>)>, format: Format) -> Result<()> {
+    let rows: Vec<HistoryRow> = history
+        .into_iter()
+        .map(|(post, track, playlists)| HistoryRow::new(post, track, playlists))
+        .collect();
+
+    match format {
+        Format::Table => print_table(&rows),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            for row in rows.iter() {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn print_table(rows: &[HistoryRow]) {
+    let header = ["ID", "DATE", "TRACK", "PLAYLIST", "DESTINATION", "STATUS"];
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.id.to_string(),
+                row.posted_at.clone(),
+                row.track.clone(),
+                row.playlist.clone(),
+                row.destination.clone(),
+                row.status.clone(),
+            ]
+        })
+        .collect();
+
+    table::print(&header, &cells);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn parses_a_date_as_midnight() {
+        let since = parse_since("2021-04-01").unwrap();
+        let midnight = NaiveDate::from_ymd_opt(2021, 4, 1).and_then(|d| d.and_hms_opt(0, 0, 0));
+        assert_eq!(Some(since), midnight);
+    }
+
+    #[test]
+    fn parses_a_date_and_time() {
+        let since = parse_since("2021-04-01 09:30:00").unwrap();
+        let expected = NaiveDate::from_ymd_opt(2021, 4, 1).and_then(|d| d.and_hms_opt(9, 30, 0));
+        assert_eq!(Some(since), expected);
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        assert!(parse_since("yesterday").is_err());
+        assert!(parse_since("2021-02-30").is_err());
+    }
+
+    #[test]
+    fn shows_an_unknown_date_for_posts_made_before_posts_were_recorded() {
+        let track = Track {
+            id: 1,
+            spotify_id: "4uLU6hMCjMI75M1A2tKUQC".to_string(),
+            name: "Time (You and I)".to_string(),
+            url: "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC".to_string(),
+            album_id: None,
+            duration_ms: None,
+            explicit: None,
+            popularity: None,
+            isrc: None,
+        };
+        let post = Post {
+            id: 1,
+            track_id: track.id,
+            destination: "twitter".to_string(),
+            remote_id: None,
+            remote_url: None,
+            posted_at: None,
+            status: "posted".to_string(),
+            playlist_id: None,
+            strategy: None,
+            recycled_at: None,
+            undone_post_id: None,
+        };
+
+        let row = HistoryRow::new(post, track, Vec::new());
+
+        assert_eq!(row.posted_at, "unknown");
+    }
+}
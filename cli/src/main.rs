@@ -1,48 +1,120 @@
-use anyhow::{Context, Result};
-use diesel::result::{DatabaseErrorKind, Error};
+mod config;
+mod history;
+mod queue;
+mod schedule;
+mod selection;
+mod table;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use dotenv::dotenv;
-use oauth;
-use rand::Rng;
-use reqwest::{blocking::Client, header};
-use std::{collections::HashMap, env};
-use structopt::StructOpt;
+use std::{env, path::Path};
+use structopt::{clap::ArgGroup, StructOpt};
 
 use database::{
-    self, establish_connection, get_playlist_offset, get_playlists, insert_playlist,
-    insert_playlist_offset, insert_track, mark_track_as_posted, update_playlist_offset,
+    self,
+    models::{NewAlbum, NewArtist, NewTrackDetails, PlaylistEntry, PostStatus, Selection, Track},
+    Backend, Store,
 };
-use spotify::{self, authenticate, refresh_access_token};
-
-const POST_TWEET_URL: &str = "https://api.twitter.com/1.1/statuses/update.json";
+use publisher::{template, Publisher};
+use spotify::{self, authenticate, refresh_access_token, SpotifyTrack};
 
-#[derive(oauth::Request)]
-struct Tweet {
-    status: String,
-}
+use config::{Config, Destination};
+use history::Format;
+use schedule::{Scheduler, Task};
+use selection::{Age, Exhaustion};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "Noiisseur", about = "Options for running Noiisseur.")]
 enum Command {
+    /// Creates a new database with all of its tables
+    Init(InitOpts),
     /// Authenticate application
-    Auth,
+    Auth {
+        #[structopt(subcommand)]
+        service: Option<AuthCmd>,
+    },
     /// All commands related to records
     Tracks(TrackCmd),
     /// All commands related to playlists
     Playlist(PlaylistCmd),
+    /// Lists past posts
+    History(HistoryOpts),
+    /// All commands related to published posts
+    Post(PostCmd),
+    /// All commands related to posts queued for a set time
+    Queue(QueueCmd),
+    /// Updates tracks and posts on the schedules in the config until stopped
+    Run,
+}
+
+#[derive(Debug, StructOpt)]
+struct InitOpts {
+    /// Where to create the database. Defaults to DATABASE_URL
+    #[structopt(long)]
+    db: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+enum AuthCmd {
+    /// Authenticate with Spotify to read playlists. This is the default
+    Spotify,
+    /// Authenticate with Twitter to post using the v2 API
+    Twitter,
 }
 
 #[derive(Debug, StructOpt)]
 enum TrackCmd {
-    // Posts the song to Twitter
-    Post,
+    // Posts the song to the configured destinations
+    Post(PostOpts),
+    /// Prints the next tracks that would be posted without posting them
+    Pick(PickOpts),
+    /// Puts posted tracks back in the pool so they can be posted again
+    Reset(ResetOpts),
     // Updates the songs in the database
     Update,
 }
 
+#[derive(Debug, StructOpt)]
+struct PostOpts {
+    /// Print the post for each destination without sending it
+    #[structopt(long)]
+    preview: bool,
+    /// Print the chance of each playlist and track being picked
+    #[structopt(long)]
+    explain: bool,
+    /// Seed the random pick so it can be reproduced. Overrides the seed in the config
+    #[structopt(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Debug, StructOpt)]
+struct PickOpts {
+    /// Seed the random picks so they can be reproduced. Overrides the seed in the config
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// How many picks to show
+    #[structopt(long, default_value = "1")]
+    count: usize,
+}
+
+#[derive(Debug, StructOpt)]
+struct ResetOpts {
+    /// Only reset tracks in the playlist with this Spotify id or name
+    #[structopt(long)]
+    playlist: Option<String>,
+    /// Only reset tracks last posted longer ago than this e.g. 180d
+    #[structopt(long)]
+    older_than: Option<Age>,
+}
+
 #[derive(Debug, StructOpt)]
 enum PlaylistCmd {
     Add(PlaylistInfo),
-    Remove,
+    /// Removes a playlist along with its unposted tracks
+    Remove(PlaylistRemove),
+    /// Sets how likely a playlist is to be picked relative to the others
+    SetWeight(PlaylistWeight),
 }
 
 #[derive(Debug, StructOpt)]
@@ -51,121 +123,1278 @@ struct PlaylistInfo {
     spotify_id: String,
 }
 
+#[derive(Debug, StructOpt)]
+struct PlaylistRemove {
+    /// The Spotify id or name of the playlist
+    playlist: String,
+    /// Print what would be removed without removing anything
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct PlaylistWeight {
+    /// The Spotify id or name of the playlist
+    playlist: String,
+    /// Playlists start with a weight of 1. A playlist with a weight of 0 is never picked
+    weight: u16,
+}
+
+#[derive(Debug, StructOpt)]
+enum PostCmd {
+    /// Deletes a published track from every destination and puts it back in the pool
+    Undo(UndoOpts),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(group = ArgGroup::with_name("post").required(true))]
+struct UndoOpts {
+    /// The id of one of the track's posts, as shown by `noi history`
+    #[structopt(group = "post")]
+    id: Option<i32>,
+    /// Undo the most recent post
+    #[structopt(long, group = "post")]
+    last: bool,
+    /// Only undo the post locally, leaving it up at the destination
+    #[structopt(long)]
+    keep_remote: bool,
+}
+
+#[derive(Debug, StructOpt)]
+enum QueueCmd {
+    /// Queues a track to be posted at a set time
+    Add(QueueAdd),
+    /// Lists the queued posts, the first one due first
+    List,
+    /// Removes a post from the queue
+    Remove(QueueRemove),
+}
+
+#[derive(Debug, StructOpt)]
+struct QueueAdd {
+    /// The Spotify id, link or URI of the track
+    track: String,
+    /// When to post the track e.g. "2026-10-24 09:00", in the timezone of the schedule
+    #[structopt(long)]
+    at: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct QueueRemove {
+    /// The id of the queued post, as shown by `noi queue list`
+    id: i32,
+}
+
+#[derive(Debug, StructOpt)]
+struct HistoryOpts {
+    /// Only show posts from this date onwards e.g. 2021-04-01
+    #[structopt(long, parse(try_from_str = history::parse_since))]
+    since: Option<NaiveDateTime>,
+    /// Only show posts from the playlist with this Spotify id or name
+    #[structopt(long)]
+    playlist: Option<String>,
+    /// The maximum number of posts to show
+    #[structopt(long, default_value = "20")]
+    limit: u32,
+    /// How to print the posts
+    #[structopt(long, default_value = "table", possible_values = &["table", "json", "csv"])]
+    format: Format,
+}
+
 fn main() -> Result<()> {
     dotenv().ok();
+    let config = Config::load()?;
+    let command = Command::from_args();
 
-    let conn = establish_connection().with_context(|| "Could not establish connection!")?;
-    match Command::from_args() {
-        Command::Auth => {
-            authenticate()?;
-            Ok(())
+    // There's no database to connect to yet
+    if let Command::Init(InitOpts { db }) = command {
+        let db_url = env::var("DATABASE_URL").ok();
+        let path = db.or_else(|| db_url.clone()).with_context(|| {
+            "Pass --db or set DATABASE_URL to choose where to create the database"
+        })?;
+        // Postgres databases are created by the server, only their tables are created here
+        if Backend::from_url(&path) == Backend::Sqlite && Path::new(&path).exists() {
+            bail!("{} already exists", path);
         }
+
+        database::connect(&path, &config.database)
+            .with_context(|| format!("Could not create database {}", path))?;
+        println!("Created database {}", path);
+        if db_url.as_deref() != Some(path.as_str()) {
+            println!("Set DATABASE_URL={} in .env to use it", path);
+        }
+        return Ok(());
+    }
+
+    let store =
+        database::establish(&config.database).with_context(|| "Could not establish connection!")?;
+    let store = store.as_ref();
+    match command {
+        Command::Init(_) => unreachable!("init is handled before connecting"),
+        Command::Auth { service } => match service.unwrap_or(AuthCmd::Spotify) {
+            AuthCmd::Spotify => {
+                authenticate()?;
+                Ok(())
+            }
+            AuthCmd::Twitter => publisher::twitter::authenticate(&config.twitter()),
+        },
         Command::Playlist(playlist_cmd) => match playlist_cmd {
             PlaylistCmd::Add(PlaylistInfo { name, spotify_id }) => {
-                let playlist_id = insert_playlist(&conn, &name, &spotify_id);
-                insert_playlist_offset(&conn, playlist_id, 0);
-                println!("Added playlist {} with id {}", name, spotify_id);
+                match store.insert_playlist(&name, &spotify_id) {
+                    Ok(_) => println!("Added playlist {} with id {}", name, spotify_id),
+                    Err(database::Error::Duplicate(_)) => {
+                        bail!("A playlist with id {} has already been added", spotify_id)
+                    }
+                    Err(e) => return Err(e.into()),
+                }
                 Ok(())
             }
-            PlaylistCmd::Remove => Ok(()),
-        },
-        Command::Tracks(track_cmd) => match track_cmd {
-            TrackCmd::Post => {
-                let twitter_consumer_key = env::var("TWITTER_CONSUMER_KEY")
-                    .expect("Missing environment variable: TWITTER_CONSUMER_KEY");
-                let twitter_consumer_secret = env::var("TWITTER_CONSUMER_SECRET")
-                    .expect("Missing environment variable: TWITTER_CONSUMER_SECRET");
-                let twitter_access_token = env::var("TWITTER_ACCESS_TOKEN")
-                    .expect("Missing environment variable: TWITTER_ACCESS_TOKEN");
-                let twitter_access_token_secret = env::var("TWITTER_ACCESS_TOKEN_SECRET")
-                    .expect("Missing environment variable: TWITTER_ACCESS_TOKEN_SECRET");
-
-                let token = oauth::Token::from_parts(
-                    twitter_consumer_key,
-                    twitter_consumer_secret,
-                    twitter_access_token,
-                    twitter_access_token_secret,
-                );
+            PlaylistCmd::SetWeight(PlaylistWeight { playlist, weight }) => {
+                let playlist = store
+                    .get_playlist(&playlist)?
+                    .with_context(|| format!("No playlist found matching {}", playlist))?;
 
-                let conn =
-                    establish_connection().with_context(|| "Could not establish connection!")?;
-                let tracks = database::get_tracks(&conn);
-                let idx: usize = rand::thread_rng().gen_range(0..tracks.len());
-                let track = tracks.get(idx).unwrap(); // This should never fail so can unwrap
+                store.set_playlist_weight(playlist.id, i32::from(weight))?;
+                println!("Set the weight of playlist {} to {}", playlist.name, weight);
+                Ok(())
+            }
+            PlaylistCmd::Remove(PlaylistRemove { playlist, dry_run }) => {
+                let playlist = store
+                    .get_playlist(&playlist)?
+                    .with_context(|| format!("No playlist found matching {}", playlist))?;
 
-                let client = Client::new();
-                let request = Tweet {
-                    status: track.url.clone(),
+                let removal = if dry_run {
+                    store.get_playlist_removal(playlist.id)?
+                } else {
+                    store.remove_playlist(playlist.id)?
                 };
-                // Creates the authentication header
-                let auth_header = oauth::post(POST_TWEET_URL, &request, &token, oauth::HmacSha1);
-
-                // Tweet the song
-                let mut params = HashMap::new();
-                params.insert("status", track.url.clone());
-                let response = client
-                    .post(POST_TWEET_URL)
-                    .header(header::AUTHORIZATION, auth_header)
-                    .form(&params)
-                    .send()?;
-
-                if response.status().is_success() {
-                    mark_track_as_posted(&conn, track);
-                    println!("Successfully tweeted song: {}", track.name);
-                    Ok(())
+
+                let (removed, kept) = if dry_run {
+                    ("Would remove", "Would keep")
                 } else {
-                    println!("Failed to tweet song: {}", track.name);
-                    println!("Got response: {}", response.text().unwrap());
-                    std::process::exit(1)
-                }
+                    ("Removed", "Kept")
+                };
+                println!(
+                    "{} playlist {} with id {}",
+                    removed, playlist.name, playlist.spotify_id
+                );
+                println!("{} {} unposted tracks", removed, removal.unposted);
+                println!("{} {} posted tracks as history", kept, removal.posted);
+                println!(
+                    "{} {} tracks that are in other playlists",
+                    kept, removal.shared
+                );
+                Ok(())
             }
-            TrackCmd::Update => {
-                let access_token = refresh_access_token()?;
-                let conn =
-                    establish_connection().with_context(|| "Could not establish connection!")?;
-
-                let playlists = get_playlists(&conn);
-                for (idx, playlist) in playlists.iter().enumerate() {
-                    println!(
-                        "Processing playlist: {} - [{}]/[{}]",
-                        playlist.name,
-                        idx + 1,
-                        playlists.len()
+        },
+        Command::History(HistoryOpts {
+            since,
+            playlist,
+            limit,
+            format,
+        }) => {
+            let playlist_id = match playlist {
+                Some(playlist) => Some(
+                    store
+                        .get_playlist(&playlist)?
+                        .with_context(|| format!("No playlist found matching {}", playlist))?
+                        .id,
+                ),
+                None => None,
+            };
+
+            let history = store.get_post_history(since, playlist_id, i64::from(limit))?;
+            history::print(history, format)
+        }
+        Command::Post(PostCmd::Undo(opts)) => {
+            // Posts that are only undone locally aren't deleted from anywhere
+            let publishers = if opts.keep_remote {
+                Vec::new()
+            } else {
+                publishers(&config)?
+            };
+            undo_post(store, &publishers, &opts)
+        }
+        Command::Queue(queue_cmd) => match queue_cmd {
+            QueueCmd::Add(QueueAdd { track, at }) => {
+                let timezone = config.schedule.timezone()?;
+                let scheduled_at = queue::parse_at(&at, timezone)?;
+                let spotify_id = queue::parse_track(&track);
+                let track = store
+                    .get_track_by_spotify_id(&spotify_id)?
+                    .with_context(|| format!("No track found matching {}", track))?;
+                if store.is_track_posted(track.id)? {
+                    bail!(
+                        "{} has already been posted, it can only be queued once it's back in the pool",
+                        track.name
                     );
-                    let offset = get_playlist_offset(&conn, playlist.id);
-                    let tracks = spotify::get_tracks(&access_token, &playlist.spotify_id, offset)
-                        .with_context(|| "Unable to get fetch all tracks from Spotify")?;
-
-                    for (idx, track) in tracks.iter().enumerate() {
-                        println!("Inserting track [{}]/[{}]", idx, tracks.len());
-                        if track.is_null() {
-                            continue;
-                        };
-                        let result = insert_track(
-                            &conn,
-                            &track.spotify_id().unwrap(),
-                            playlist.id,
-                            &track.name().unwrap(),
-                            &track.url().unwrap(),
-                        );
-
-                        match result {
-                            Ok(_) => continue,
-                            // It's possible for the same song to exist in multiple playlists. Currently,
-                            // we don't actually want to store duplicates in the `tracks` table. Therefore
-                            // we just ignore these cases. They're not common so this solution is fine
-                            Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
-                                continue
-                            }
-                            Err(e) => panic!("Error {} occurred", e),
-                        }
-                    }
+                }
 
-                    let new_offset = offset + tracks.len() as i32;
-                    update_playlist_offset(&conn, playlist.id, new_offset);
+                let id = store.insert_scheduled_post(track.id, scheduled_at)?;
+                println!(
+                    "Queued {} to be posted at {} with id {}",
+                    track.name,
+                    timezone
+                        .from_utc_datetime(&scheduled_at)
+                        .format("%Y-%m-%d %H:%M %Z"),
+                    id
+                );
+                Ok(())
+            }
+            QueueCmd::List => {
+                queue::print(store.get_scheduled_posts()?, config.schedule.timezone()?);
+                Ok(())
+            }
+            QueueCmd::Remove(QueueRemove { id }) => {
+                if store.delete_scheduled_post(id)? == 0 {
+                    bail!("No queued post with id {}", id);
                 }
+                println!("Removed queued post {}", id);
                 Ok(())
             }
         },
+        Command::Run => run_schedule(store, &config, &publishers(&config)?),
+        Command::Tracks(track_cmd) => match track_cmd {
+            TrackCmd::Post(opts) => post_track(store, &config, &publishers(&config)?, &opts, None),
+            TrackCmd::Pick(opts) => pick_tracks(store, &config, &opts),
+            TrackCmd::Reset(ResetOpts {
+                playlist,
+                older_than,
+            }) => {
+                let playlist = match playlist {
+                    Some(playlist) => Some(
+                        store
+                            .get_playlist(&playlist)?
+                            .with_context(|| format!("No playlist found matching {}", playlist))?,
+                    ),
+                    None => None,
+                };
+                let posted_before = older_than.map(|age| age.ago()).transpose()?;
+
+                let reset =
+                    store.reset_posted_tracks(playlist.as_ref().map(|p| p.id), posted_before)?;
+                println!("Put {} posted tracks back in the pool", reset);
+                Ok(())
+            }
+            TrackCmd::Update => {
+                let access_token = refresh_access_token()?;
+                update_tracks(store, &access_token)
+            }
+        },
+    }
+}
+
+/// Builds the publisher of every configured destination
+fn publishers(config: &Config) -> Result<Vec<(Destination, Box<dyn Publisher>)>> {
+    config
+        .destinations()
+        .into_iter()
+        .map(|destination| {
+            let publisher = destination.publisher.build()?;
+            Ok((destination, publisher))
+        })
+        .collect()
+}
+
+/// Posts a track to every destination. `queued_id` is the id of the queued
+/// post to post. Without one, a queued post that's due is posted first,
+/// otherwise the track is picked using the strategy set in the config
+fn post_track(
+    store: &dyn Store,
+    config: &Config,
+    publishers: &[(Destination, Box<dyn Publisher>)],
+    opts: &PostOpts,
+    queued_id: Option<i32>,
+) -> Result<()> {
+    let now = Utc::now().naive_utc();
+    let mut due = None;
+    for (scheduled, track) in store.get_scheduled_posts()? {
+        match queued_id {
+            Some(id) if scheduled.id != id => continue,
+            None if scheduled.scheduled_at > now => break,
+            _ => {}
+        }
+        // The track can have been picked and posted since it was queued
+        if store.is_track_posted(track.id)? {
+            println!(
+                "Skipped queued post {} as {} has already been posted",
+                scheduled.id, track.name
+            );
+            if !opts.preview {
+                store.delete_scheduled_post(scheduled.id)?;
+            }
+            // A skipped queued post isn't swapped for a picked track
+            if queued_id.is_some() {
+                return Ok(());
+            }
+            continue;
+        }
+        due = Some((scheduled, track));
+        break;
+    }
+    let (track, playlist_id, strategy_name, queued) = match (due, queued_id) {
+        (Some((scheduled, track)), _) => {
+            if opts.explain {
+                println!(
+                    "Picked {}, which was queued to be posted at {}",
+                    track.name,
+                    config
+                        .schedule
+                        .timezone()?
+                        .from_utc_datetime(&scheduled.scheduled_at)
+                        .format("%Y-%m-%d %H:%M %Z")
+                );
+            }
+            (track, None, "queue".to_string(), Some(scheduled.id))
+        }
+        (None, Some(id)) => bail!("Queued post {} is no longer queued", id),
+        (None, None) => {
+            let (track, playlist_id, strategy_name) = pick_track(store, config, opts)?;
+            (track, Some(playlist_id), strategy_name, None)
+        }
+    };
+    let track = &track;
+
+    let mut details = store.get_track_details(track)?;
+    // The playlist the track was picked from comes first so it's the one in the post
+    if let Some(playlist_id) = playlist_id {
+        details.playlists.sort_by_key(|p| p.id != playlist_id);
+    }
+
+    let render = |destination, publisher: &dyn Publisher| {
+        let text = template::render(config.template(destination), &details);
+        match publisher.text_limit() {
+            Some(limit) => template::truncate(&text, &track.url, limit),
+            None => text,
+        }
+    };
+
+    if opts.preview {
+        for (destination, publisher) in publishers.iter() {
+            println!("[{}]", destination.name());
+            println!("{}", render(destination, publisher.as_ref()));
+        }
+        return Ok(());
+    }
+
+    let selection = Selection {
+        playlist_id,
+        strategy: &strategy_name,
+    };
+    let mut required_failed = false;
+    let mut results = Vec::new();
+    for (destination, publisher) in publishers.iter() {
+        let text = render(destination, publisher.as_ref());
+        let result = publisher.publish(&details, &text);
+        match &result {
+            Ok(receipt) => {
+                println!(
+                    "Successfully posted song to {}: {}",
+                    destination.name(),
+                    track.name
+                );
+                if let Some(url) = &receipt.remote_url {
+                    println!("Post: {}", url);
+                }
+            }
+            Err(e) => {
+                required_failed |= destination.required;
+                println!(
+                    "Failed to post song to {}: {}",
+                    destination.name(),
+                    track.name
+                );
+                println!("Got error: {}", e);
+            }
+        }
+        results.push((destination.name(), result));
+    }
+
+    for (destination, result) in results.iter() {
+        match result {
+            // The track stays in the pool so it's retried on the next run
+            Ok(receipt) if required_failed => store.insert_post(
+                track.id,
+                destination,
+                receipt.remote_id.as_deref(),
+                receipt.remote_url.as_deref(),
+                PostStatus::Incomplete,
+                &selection,
+            )?,
+            Ok(receipt) => store.mark_track_as_posted(
+                track,
+                destination,
+                receipt.remote_id.as_deref(),
+                receipt.remote_url.as_deref(),
+                &selection,
+            )?,
+            Err(_) => store.insert_post(
+                track.id,
+                destination,
+                None,
+                None,
+                PostStatus::Failed,
+                &selection,
+            )?,
+        };
+    }
+
+    if required_failed {
+        bail!("Could not post to every required destination");
+    }
+    // A queued post that failed stays queued so it's retried on the next run
+    if let Some(scheduled_id) = queued {
+        store.delete_scheduled_post(scheduled_id)?;
+    }
+    Ok(())
+}
+
+/// Deletes a post from every destination the track was posted to along with
+/// it, then puts the track back in the pool. A post that fails to delete stays
+/// live, and the track out of the pool, so the undo can be run again
+fn undo_post(
+    store: &dyn Store,
+    publishers: &[(Destination, Box<dyn Publisher>)],
+    opts: &UndoOpts,
+) -> Result<()> {
+    let post = if opts.last {
+        store
+            .get_last_post()?
+            .with_context(|| "There are no posts to undo")?
+    } else {
+        let id = opts.id.expect("either an id or --last is required");
+        store
+            .get_post(id)?
+            .with_context(|| format!("No post with id {}", id))?
+    };
+    if post.status != PostStatus::Posted.as_str() {
+        bail!(
+            "Post {} can't be undone, its status is {}",
+            post.id,
+            post.status
+        );
+    }
+    let track = store.get_track(post.track_id)?;
+    // The track was posted to every destination at once and only goes
+    // back in the pool once all of those posts are undone
+    let posts = store.get_published_posts(&post)?;
+    if !posts.iter().any(|published| published.id == post.id) {
+        bail!("Post {} has already been undone", post.id);
+    }
+
+    // Every post is checked before any is deleted, so a post that can't be
+    // deleted at all doesn't leave the track half undone
+    let deletes = if opts.keep_remote {
+        Vec::new()
+    } else {
+        posts
+            .iter()
+            .map(|post| {
+                let remote_id = post.remote_id.as_deref().with_context(|| {
+                    format!(
+                        "Post {} has no remote id so it can't be deleted from {}. \
+                        Use --keep-remote to only undo it locally",
+                        post.id, post.destination
+                    )
+                })?;
+                let (_, publisher) = publishers
+                    .iter()
+                    .find(|(destination, _)| destination.name() == post.destination)
+                    .with_context(|| {
+                        format!(
+                            "Post {} can't be deleted, {} is not a configured destination. \
+                            Use --keep-remote to only undo it locally",
+                            post.id, post.destination
+                        )
+                    })?;
+                Ok((post, remote_id, publisher))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut undone = if opts.keep_remote {
+        posts.clone()
+    } else {
+        Vec::new()
+    };
+    let mut failed = Vec::new();
+    for (post, remote_id, publisher) in deletes {
+        match publisher.unpublish(remote_id) {
+            Ok(()) => {
+                println!("Deleted post from {}: {}", post.destination, track.name);
+                undone.push(post.clone());
+            }
+            Err(e) => {
+                println!(
+                    "Failed to delete post from {}: {}",
+                    post.destination, track.name
+                );
+                println!("Got error: {}", e);
+                failed.push(post.destination.as_str());
+            }
+        }
+    }
+
+    // The posts that were deleted are undone together, even when others failed
+    store.undo_posts(&undone)?;
+    for post in undone.iter() {
+        println!("Undid post {} of song: {}", post.id, track.name);
+    }
+    if !failed.is_empty() {
+        bail!(
+            "Could not delete the posts from {}, {} stays out of the pool until they're undone",
+            failed.join(", "),
+            track.name
+        );
+    }
+    println!("Put {} back in the pool", track.name);
+    Ok(())
+}
+
+/// Picks a track to post using the strategy set in the config. Returns the
+/// track along with the id of the playlist it was picked from and the name of
+/// the strategy
+fn pick_track(store: &dyn Store, config: &Config, opts: &PostOpts) -> Result<(Track, i32, String)> {
+    let mut tracks_by_playlist = store.get_tracks_by_playlist()?;
+    if tracks_by_playlist
+        .iter()
+        .all(|(_, entries)| entries.is_empty())
+    {
+        if opts.preview && config.selection.exhaustion != Exhaustion::Error {
+            bail!("Every track has been posted and a preview doesn't put tracks back in the pool");
+        }
+        config.selection.refill(store)?;
+        tracks_by_playlist = store.get_tracks_by_playlist()?;
+    }
+    let strategy = config.selection.build(store)?;
+    let pool = selection::pool(&tracks_by_playlist);
+    let odds = strategy.odds(&pool);
+    if opts.explain {
+        println!("Strategy: {}", strategy.name());
+        selection::explain(&pool, &odds);
+    }
+    let mut rng = selection::rng(opts.seed.or(config.selection.seed), &pool);
+    let picked = selection::pick(&odds, &mut rng)
+        .with_context(|| "Every playlist with tracks left to post has a weight of 0")?;
+    let (playlist, track) = (picked.playlist, &picked.entry.track);
+    if opts.explain {
+        println!(
+            "Picked {} from {}, which had a {} chance of being picked",
+            track.name,
+            playlist.name,
+            selection::percent(selection::track_chance(&odds, track))
+        );
+    }
+
+    Ok((track.clone(), playlist.id, strategy.name()))
+}
+
+/// Runs the scheduled tasks and posts queued posts when they're due until
+/// stopped. A task that fails is reported and runs again on its next scheduled time
+fn run_schedule(
+    store: &dyn Store,
+    config: &Config,
+    publishers: &[(Destination, Box<dyn Publisher>)],
+) -> Result<()> {
+    let mut scheduler = Scheduler::new(&config.schedule, store)?;
+    if scheduler.is_empty() && store.get_scheduled_posts()?.is_empty() {
+        bail!(
+            "Nothing is scheduled. Set `update` or `post` in the `schedule` section of the config \
+            or queue a post with `noi queue add`"
+        );
+    }
+    for job in scheduler.jobs() {
+        scheduler.log(&format!(
+            "Next {} at {}",
+            job.task.as_str(),
+            scheduler.local(job.next)
+        ));
+    }
+    if let Some((_, queued)) = scheduler.queued(store)? {
+        scheduler.log(&format!("Next queued post at {}", scheduler.local(queued)));
+    }
+
+    loop {
+        let task = scheduler.wait(store)?;
+        let started_at = Utc::now();
+        scheduler.log(&format!("Running {}", task.as_str()));
+        let opts = PostOpts {
+            preview: false,
+            explain: false,
+            seed: None,
+        };
+        let result = match task {
+            Task::Update => refresh_access_token().and_then(|token| update_tracks(store, &token)),
+            // A post run posts the queued post that's due before picking a track
+            Task::Post => post_track(store, config, publishers, &opts, None),
+            Task::Queue(id) => post_track(store, config, publishers, &opts, Some(id)),
+        };
+        match &result {
+            Ok(()) => scheduler.log(&format!("Finished {}", task.as_str())),
+            Err(e) => scheduler.log(&format!("{} failed: {:#}", task.as_str(), e)),
+        }
+
+        scheduler.ran(task, store, started_at, result.is_ok())?;
+        if let Some(job) = scheduler.jobs().iter().find(|job| job.task == task) {
+            scheduler.log(&format!(
+                "Next {} at {}",
+                task.as_str(),
+                scheduler.local(job.next)
+            ));
+        }
+        if let Task::Queue(_) = task {
+            if let Some((_, queued)) = scheduler.queued(store)? {
+                scheduler.log(&format!("Next queued post at {}", scheduler.local(queued)));
+            }
+        }
+    }
+}
+
+/// Prints the next tracks that would be posted, each picked as if the ones
+/// before it were posted
+fn pick_tracks(store: &dyn Store, config: &Config, opts: &PickOpts) -> Result<()> {
+    let picks = next_picks(store, config, opts)?;
+    if picks.is_empty() {
+        bail!("There are no tracks left that can be picked");
+    }
+
+    let header = ["#", "TRACK", "PLAYLIST", "CHANCE"];
+    let rows: Vec<Vec<String>> = picks
+        .iter()
+        .enumerate()
+        .map(|(idx, (track, playlist, chance))| {
+            vec![
+                (idx + 1).to_string(),
+                track.name.clone(),
+                playlist.clone().unwrap_or_else(|| "(queued)".to_string()),
+                selection::percent(*chance),
+            ]
+        })
+        .collect();
+    table::print(&header, &rows);
+
+    if picks.len() < opts.count {
+        println!("Only {} tracks are left that can be picked", picks.len());
+    }
+    Ok(())
+}
+
+/// Works out the next tracks `noi tracks post` would post, along with the name
+/// of the playlist each is picked from and its chance of being picked. Queued
+/// posts that are due come first, as they're posted before any track is picked
+fn next_picks(
+    store: &dyn Store,
+    config: &Config,
+    opts: &PickOpts,
+) -> Result<Vec<(Track, Option<String>, f64)>> {
+    let now = Utc::now().naive_utc();
+    let mut queued = Vec::new();
+    for (scheduled, track) in store.get_scheduled_posts()? {
+        if scheduled.scheduled_at > now || queued.len() == opts.count {
+            break;
+        }
+        // Queued tracks that have already been posted are skipped by the post
+        if !store.is_track_posted(track.id)? {
+            queued.push(track);
+        }
+    }
+
+    let mut tracks_by_playlist = store.get_tracks_by_playlist()?;
+    let mut strategy = config.selection.build(store)?;
+    let mut picks = Vec::new();
+    for track in queued {
+        let details = store.get_track_details(&track)?;
+        let entry = PlaylistEntry {
+            track,
+            added_at: None,
+            artist_ids: details.artists.iter().map(|artist| artist.id).collect(),
+        };
+        strategy.picked(None, &entry);
+        for (_, entries) in tracks_by_playlist.iter_mut() {
+            entries.retain(|other| other.track.id != entry.track.id);
+        }
+        picks.push((entry.track, None, 1.0));
+    }
+
+    let picked = selection::simulate(
+        strategy.as_mut(),
+        selection::pool(&tracks_by_playlist),
+        opts.count - picks.len(),
+        opts.seed.or(config.selection.seed),
+    );
+    picks.extend(picked.into_iter().map(|picked| {
+        (
+            picked.entry.track.clone(),
+            Some(picked.playlist.name.clone()),
+            picked.chance,
+        )
+    }));
+    Ok(picks)
+}
+
+/// Syncs the tracks of every playlist with Spotify
+fn update_tracks(store: &dyn Store, access_token: &str) -> Result<()> {
+    let playlists = store.get_playlists()?;
+    let (mut added, mut removed) = (0, 0);
+    for (idx, playlist) in playlists.iter().enumerate() {
+        println!(
+            "Processing playlist: {} - [{}]/[{}]",
+            playlist.name,
+            idx + 1,
+            playlists.len()
+        );
+        let snapshot_id = spotify::get_snapshot_id(access_token, &playlist.spotify_id)
+            .with_context(|| "Unable to get the playlist from Spotify")?;
+        let sync = store.get_playlist_sync(playlist.id)?;
+        if matches!(sync, Some(sync) if sync.snapshot_id == snapshot_id) {
+            println!("Playlist is unchanged");
+            continue;
+        }
+
+        // Tracks can be added anywhere in a playlist, moved or removed so we fetch
+        // every track and let the sync work out what changed
+        let tracks = spotify::get_tracks(access_token, &playlist.spotify_id)
+            .with_context(|| "Unable to get fetch all tracks from Spotify")?;
+        let tracks: Vec<NewTrackDetails> = tracks.iter().filter_map(track_details).collect();
+
+        // The same song can be in multiple playlists. It's only stored once in the
+        // `tracks` table and added to each playlist it's in
+        let report = store
+            .sync_playlist(playlist.id, &snapshot_id, &tracks)
+            .with_context(|| format!("Unable to sync playlist {}", playlist.name))?;
+        println!(
+            "Added {} tracks and removed {} tracks",
+            report.added, report.removed
+        );
+        added += report.added;
+        removed += report.removed;
+    }
+    println!(
+        "Added {} tracks and removed {} tracks across {} playlists",
+        added,
+        removed,
+        playlists.len()
+    );
+    Ok(())
+}
+
+/// Converts a track fetched from Spotify into one that can be stored. Tracks
+/// that are no longer available on Spotify are skipped
+fn track_details(track: &SpotifyTrack) -> Option<NewTrackDetails<'_>> {
+    let added_at = track.added_at.as_deref().and_then(|added_at| {
+        DateTime::parse_from_rfc3339(added_at)
+            .ok()
+            .map(|added_at| added_at.naive_utc())
+    });
+    let added_by = track.added_by.as_ref().map(|user| user.id.as_str());
+    let track = track.details()?;
+    let album = track.album.as_ref().and_then(|album| {
+        Some(NewAlbum {
+            spotify_id: album.spotify_id.as_deref()?,
+            name: &album.name,
+            release_date: album.release_date.as_deref(),
+            image_url: album.images.first().map(|image| image.url.as_str()),
+            thumbnail_url: album.images.last().map(|image| image.url.as_str()),
+        })
+    });
+    let artists = track
+        .artists
+        .iter()
+        .filter_map(|artist| {
+            Some(NewArtist {
+                spotify_id: artist.spotify_id.as_deref()?,
+                name: &artist.name,
+            })
+        })
+        .collect();
+
+    Some(NewTrackDetails {
+        spotify_id: &track.spotify_id,
+        name: &track.name,
+        url: track.url(),
+        duration_ms: track.duration_ms,
+        explicit: track.explicit,
+        popularity: track.popularity,
+        isrc: track.isrc(),
+        album,
+        artists,
+        added_at,
+        added_by,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use chrono::Duration;
+
+    use database::{
+        models::{PostStatus, TrackDetails},
+        MemoryStore,
+    };
+    use publisher::PublishReceipt;
+
+    use super::*;
+
+    const POST: PostOpts = PostOpts {
+        preview: false,
+        explain: false,
+        seed: None,
+    };
+
+    fn new_track(spotify_id: &str) -> NewTrackDetails<'_> {
+        NewTrackDetails {
+            spotify_id,
+            name: spotify_id,
+            url: "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+            duration_ms: None,
+            explicit: None,
+            popularity: None,
+            isrc: None,
+            album: None,
+            artists: Vec::new(),
+            added_at: None,
+            added_by: None,
+        }
+    }
+
+    /// A store with a playlist of each of the given tracks
+    fn store(tracks: &[&str]) -> MemoryStore {
+        let store = MemoryStore::new();
+        let playlist_id = store.insert_playlist("Morning", "morning").unwrap();
+        for spotify_id in tracks.iter() {
+            store
+                .save_track(playlist_id, &new_track(spotify_id))
+                .unwrap();
+        }
+        store
+    }
+
+    /// A config with a destination of each of the given names, along with
+    /// whether it's required
+    fn config(destinations: &[(&str, bool)], extra: &str) -> Config {
+        let destinations: String = destinations
+            .iter()
+            .map(|(name, required)| {
+                format!(
+                    "[[destinations]]\nname = \"{name}\"\nrequired = {required}\n\
+                    kind = \"webhook\"\nurl = \"http://localhost/{name}\"\n",
+                    name = name,
+                    required = required
+                )
+            })
+            .collect();
+        toml::from_str(&format!("{}\n{}", extra, destinations)).unwrap()
+    }
+
+    /// What the fake publishers of a test were sent
+    #[derive(Default)]
+    struct Sent {
+        /// The destination and name of each track published
+        published: Vec<(String, String)>,
+        /// The destination and remote id of each post deleted
+        deleted: Vec<(String, String)>,
+    }
+
+    /// Records what it's sent instead of sending it, or fails when told to
+    struct FakePublisher {
+        name: String,
+        fails: bool,
+        sent: Rc<RefCell<Sent>>,
+    }
+
+    impl Publisher for FakePublisher {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn publish(&self, track: &TrackDetails, _text: &str) -> Result<PublishReceipt> {
+            if self.fails {
+                bail!("{} is down", self.name);
+            }
+            self.sent
+                .borrow_mut()
+                .published
+                .push((self.name.clone(), track.track.name.clone()));
+            Ok(PublishReceipt {
+                destination: self.name.clone(),
+                remote_id: Some(track.track.spotify_id.clone()),
+                remote_url: None,
+            })
+        }
+
+        fn unpublish(&self, remote_id: &str) -> Result<()> {
+            if self.fails {
+                bail!("{} is down", self.name);
+            }
+            self.sent
+                .borrow_mut()
+                .deleted
+                .push((self.name.clone(), remote_id.to_string()));
+            Ok(())
+        }
+    }
+
+    /// Fake publishers along with what they were sent
+    type Fakes = (Vec<(Destination, Box<dyn Publisher>)>, Rc<RefCell<Sent>>);
+
+    /// A fake publisher for every destination of the config, failing for the
+    /// destinations named in `failing`
+    fn fakes(config: &Config, failing: &[&str]) -> Fakes {
+        let sent = Rc::new(RefCell::new(Sent::default()));
+        let publishers = config
+            .destinations()
+            .into_iter()
+            .map(|destination| {
+                let publisher: Box<dyn Publisher> = Box::new(FakePublisher {
+                    name: destination.name().to_string(),
+                    fails: failing.contains(&destination.name()),
+                    sent: Rc::clone(&sent),
+                });
+                (destination, publisher)
+            })
+            .collect();
+        (publishers, sent)
+    }
+
+    /// Pairs up destinations with what was sent to them, to compare with `Sent`
+    fn sent_to(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(destination, value)| (destination.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn pool(store: &dyn Store) -> Vec<String> {
+        store
+            .get_tracks()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    }
+
+    #[test]
+    fn posts_a_track_and_takes_it_out_of_the_pool() {
+        let store = store(&["time"]);
+        let config = config(&[("posted", true)], "");
+        let (publishers, sent) = fakes(&config, &[]);
+
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+
+        assert_eq!(sent.borrow().published, sent_to(&[("posted", "time")]));
+        assert!(pool(&store).is_empty());
+        let post = store.get_last_post().unwrap().unwrap();
+        assert_eq!(post.destination, "posted");
+        assert_eq!(post.remote_id.as_deref(), Some("time"));
+        assert_eq!(post.strategy.as_deref(), Some("weighted"));
+        assert!(post.playlist_id.is_some());
+    }
+
+    #[test]
+    fn keeps_the_track_in_the_pool_when_a_required_destination_fails() {
+        let store = store(&["time"]);
+        let config = config(&[("incomplete", true), ("failed", true)], "");
+        let (publishers, sent) = fakes(&config, &["failed"]);
+
+        let result = post_track(&store, &config, &publishers, &POST, None);
+
+        assert!(result.is_err());
+        assert_eq!(sent.borrow().published, sent_to(&[("incomplete", "time")]));
+        assert_eq!(pool(&store), vec!["time"]);
+        let statuses: Vec<(String, String)> = store
+            .get_post_history(None, None, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(post, _, _)| (post.destination, post.status))
+            .collect();
+        assert!(statuses.contains(&(
+            "incomplete".to_string(),
+            PostStatus::Incomplete.as_str().to_string()
+        )));
+        assert!(statuses.contains(&(
+            "failed".to_string(),
+            PostStatus::Failed.as_str().to_string()
+        )));
+    }
+
+    #[test]
+    fn posts_the_track_when_only_an_optional_destination_fails() {
+        let store = store(&["time"]);
+        let config = config(&[("required", true), ("optional", false)], "");
+        let (publishers, sent) = fakes(&config, &["optional"]);
+
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+
+        assert_eq!(sent.borrow().published, sent_to(&[("required", "time")]));
+        assert!(pool(&store).is_empty());
+        let statuses: Vec<(String, String)> = store
+            .get_post_history(None, None, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(post, _, _)| (post.destination, post.status))
+            .collect();
+        assert!(statuses.contains(&(
+            "required".to_string(),
+            PostStatus::Posted.as_str().to_string()
+        )));
+        assert!(statuses.contains(&(
+            "optional".to_string(),
+            PostStatus::Failed.as_str().to_string()
+        )));
+    }
+
+    #[test]
+    fn posts_a_due_queued_post_first() {
+        let store = store(&["time", "pelota", "white-gloves"]);
+        let pelota = store.get_track_by_spotify_id("pelota").unwrap().unwrap();
+        let due = Utc::now().naive_utc() - Duration::minutes(5);
+        store.insert_scheduled_post(pelota.id, due).unwrap();
+        let config = config(&[("queued", true)], "");
+        let (publishers, sent) = fakes(&config, &[]);
+
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+
+        assert_eq!(sent.borrow().published, sent_to(&[("queued", "pelota")]));
+        assert!(!pool(&store).contains(&"pelota".to_string()));
+        assert!(store.get_scheduled_posts().unwrap().is_empty());
+        let post = store.get_last_post().unwrap().unwrap();
+        assert_eq!(post.strategy.as_deref(), Some("queue"));
+        assert_eq!(post.playlist_id, None);
+    }
+
+    #[test]
+    fn skips_a_queued_post_that_was_already_posted() {
+        let store = store(&["time", "pelota"]);
+        let pelota = store.get_track_by_spotify_id("pelota").unwrap().unwrap();
+        let due = Utc::now().naive_utc() - Duration::minutes(5);
+        store.insert_scheduled_post(pelota.id, due).unwrap();
+        let selection = Selection {
+            playlist_id: None,
+            strategy: "weighted",
+        };
+        store
+            .mark_track_as_posted(&pelota, "webhook", None, None, &selection)
+            .unwrap();
+        let config = config(&[("skipped", true)], "");
+        let (publishers, sent) = fakes(&config, &[]);
+        let opts = PickOpts {
+            seed: None,
+            count: 2,
+        };
+
+        let picks = next_picks(&store, &config, &opts).unwrap();
+        assert_eq!(picks.len(), 1);
+        assert_eq!(picks[0].0.name, "time");
+
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+
+        assert_eq!(sent.borrow().published, sent_to(&[("skipped", "time")]));
+        assert!(store.get_scheduled_posts().unwrap().is_empty());
+        let post = store.get_last_post().unwrap().unwrap();
+        assert_eq!(post.strategy.as_deref(), Some("weighted"));
+    }
+
+    #[test]
+    fn skips_a_queued_post_that_came_due_while_not_running() {
+        let store = store(&["time", "pelota"]);
+        let pelota = store.get_track_by_spotify_id("pelota").unwrap().unwrap();
+        let due = Utc::now().naive_utc() - Duration::minutes(5);
+        store.insert_scheduled_post(pelota.id, due).unwrap();
+
+        let catch_up = config(&[("missed", true)], "[schedule]\nmissed = \"catch-up\"");
+        let scheduler = Scheduler::new(&catch_up.schedule, &store).unwrap();
+        assert!(scheduler.queued(&store).unwrap().is_some());
+
+        let config = config(&[("missed", true)], "[schedule]\nmissed = \"skip\"");
+        let (publishers, sent) = fakes(&config, &[]);
+        let scheduler = Scheduler::new(&config.schedule, &store).unwrap();
+        assert!(scheduler.queued(&store).unwrap().is_none());
+        assert!(store.get_scheduled_posts().unwrap().is_empty());
+
+        // The next post run picks a track instead
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+        assert_eq!(sent.borrow().published.len(), 1);
+        let post = store.get_last_post().unwrap().unwrap();
+        assert_eq!(post.strategy.as_deref(), Some("weighted"));
+    }
+
+    #[test]
+    fn posts_the_queued_post_that_fired_after_skipping_missed_ones() {
+        let store = store(&["time", "pelota", "white-gloves"]);
+        let track = |spotify_id| store.get_track_by_spotify_id(spotify_id).unwrap().unwrap();
+        let now = Utc::now().naive_utc();
+        store
+            .insert_scheduled_post(track("pelota").id, now - Duration::minutes(5))
+            .unwrap();
+        let next = store
+            .insert_scheduled_post(track("white-gloves").id, now + Duration::minutes(5))
+            .unwrap();
+        let config = config(&[("resumed", true)], "[schedule]\nmissed = \"skip\"");
+        let (publishers, sent) = fakes(&config, &[]);
+
+        let scheduler = Scheduler::new(&config.schedule, &store).unwrap();
+        let (fired, _) = scheduler.queued(&store).unwrap().unwrap();
+        assert_eq!(fired, next);
+
+        // Queued while running and due before the one that fired
+        let due = store
+            .insert_scheduled_post(track("time").id, now - Duration::minutes(1))
+            .unwrap();
+        post_track(&store, &config, &publishers, &POST, Some(fired)).unwrap();
+
+        assert_eq!(
+            sent.borrow().published,
+            sent_to(&[("resumed", "white-gloves")])
+        );
+        let queued: Vec<i32> = store
+            .get_scheduled_posts()
+            .unwrap()
+            .into_iter()
+            .map(|(scheduled, _)| scheduled.id)
+            .collect();
+        assert_eq!(queued, vec![due]);
+        assert!(pool(&store).contains(&"pelota".to_string()));
+    }
+
+    #[test]
+    fn previews_without_posting() {
+        let store = store(&["time"]);
+        let config = config(&[("preview", true)], "");
+        let (publishers, sent) = fakes(&config, &[]);
+        let opts = PostOpts {
+            preview: true,
+            ..POST
+        };
+
+        post_track(&store, &config, &publishers, &opts, None).unwrap();
+
+        assert!(sent.borrow().published.is_empty());
+        assert_eq!(pool(&store), vec!["time"]);
+        assert!(store.get_last_post().unwrap().is_none());
+    }
+
+    const UNDO_LAST: UndoOpts = UndoOpts {
+        id: None,
+        last: true,
+        keep_remote: false,
+    };
+
+    #[test]
+    fn undoes_a_post_at_every_destination() {
+        let store = store(&["time"]);
+        let config = config(&[("mastodon", true), ("bluesky", true)], "");
+        let (publishers, sent) = fakes(&config, &[]);
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+
+        undo_post(&store, &publishers, &UNDO_LAST).unwrap();
+
+        assert_eq!(
+            sent.borrow().deleted,
+            sent_to(&[("mastodon", "time"), ("bluesky", "time")])
+        );
+        assert_eq!(pool(&store), vec!["time"]);
+        let statuses: Vec<String> = store
+            .get_post_history(None, None, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(post, _, _)| post.status)
+            .collect();
+        assert_eq!(statuses, vec!["undone", "undone", "posted", "posted"]);
+        assert!(undo_post(&store, &publishers, &UNDO_LAST).is_err());
+    }
+
+    #[test]
+    fn keeps_the_track_out_of_the_pool_until_every_post_is_deleted() {
+        let store = store(&["time"]);
+        let config = config(&[("mastodon", true), ("bluesky", true)], "");
+        let (publishers, _) = fakes(&config, &[]);
+        post_track(&store, &config, &publishers, &POST, None).unwrap();
+        let (failing, sent) = fakes(&config, &["bluesky"]);
+
+        let error = undo_post(&store, &failing, &UNDO_LAST).unwrap_err();
+
+        assert!(error.to_string().contains("bluesky"), "{}", error);
+        assert_eq!(sent.borrow().deleted, sent_to(&[("mastodon", "time")]));
+        assert!(pool(&store).is_empty());
+
+        // Running it again only deletes the post that's still up
+        let (publishers, sent) = fakes(&config, &[]);
+        undo_post(&store, &publishers, &UNDO_LAST).unwrap();
+        assert_eq!(sent.borrow().deleted, sent_to(&[("bluesky", "time")]));
+        assert_eq!(pool(&store), vec!["time"]);
+    }
+
+    #[test]
+    fn picks_a_track_from_the_pool() {
+        let store = store(&["time", "pelota"]);
+        let playlist = store.get_playlist("morning").unwrap().unwrap();
+
+        let (track, playlist_id, strategy) = pick_track(&store, &Config::default(), &POST).unwrap();
+
+        assert!(pool(&store).contains(&track.name));
+        assert_eq!(playlist_id, playlist.id);
+        assert_eq!(strategy, "weighted");
+    }
+
+    #[test]
+    fn refills_the_pool_once_every_track_is_posted() {
+        let store = store(&["time"]);
+        let track = store.get_track_by_spotify_id("time").unwrap().unwrap();
+        let selection = Selection {
+            playlist_id: None,
+            strategy: "weighted",
+        };
+        store
+            .mark_track_as_posted(&track, "webhook", None, None, &selection)
+            .unwrap();
+
+        assert!(pick_track(&store, &Config::default(), &POST).is_err());
+
+        let config = config(&[], "[selection]\nexhaustion = \"reset\"");
+        let (picked, _, _) = pick_track(&store, &config, &POST).unwrap();
+        assert_eq!(picked.id, track.id);
+    }
+
+    #[test]
+    fn picks_the_tracks_that_are_posted_next() {
+        let strategies = ["weighted", "uniform", "round-robin", "oldest-added"];
+        for strategy in strategies.iter() {
+            let store = store(&["time", "pelota", "white-gloves"]);
+            let evening = store.insert_playlist("Evening", "evening").unwrap();
+            for spotify_id in ["so-we-wont-forget", "august-10", "maria-tambien"].iter() {
+                store.save_track(evening, &new_track(spotify_id)).unwrap();
+            }
+            let queued = store.get_track_by_spotify_id("august-10").unwrap().unwrap();
+            let due = Utc::now().naive_utc() - Duration::minutes(5);
+            store.insert_scheduled_post(queued.id, due).unwrap();
+            // Seeded in the config, like the posts a scheduler makes
+            let config = config(
+                &[("picked", true)],
+                &format!("[selection]\nstrategy = \"{}\"\nseed = 7", strategy),
+            );
+            let (publishers, sent) = fakes(&config, &[]);
+
+            let opts = PickOpts {
+                seed: None,
+                count: 4,
+            };
+            let picks: Vec<i32> = next_picks(&store, &config, &opts)
+                .unwrap()
+                .into_iter()
+                .map(|(track, _, _)| track.id)
+                .collect();
+
+            let mut posted = Vec::new();
+            for _ in 0..4 {
+                post_track(&store, &config, &publishers, &POST, None).unwrap();
+                posted.push(store.get_last_post().unwrap().unwrap().track_id);
+            }
+
+            assert_eq!(sent.borrow().published.len(), 4, "{}", strategy);
+            assert_eq!(picks[0], queued.id, "{}", strategy);
+            assert_eq!(picks, posted, "{}", strategy);
+        }
     }
 }
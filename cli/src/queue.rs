@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+use database::models::{ScheduledPost, Track};
+
+use crate::table;
+
+/// Gets the Spotify id of a track from its id, its link
+/// (`https://open.spotify.com/track/<id>`) or its URI (`spotify:track:<id>`)
+pub fn parse_track(track: &str) -> String {
+    let id = track
+        .strip_prefix("https://open.spotify.com/track/")
+        .or_else(|| track.strip_prefix("spotify:track:"))
+        .unwrap_or(track);
+    // Links copied from Spotify end with a query string
+    id.split('?').next().unwrap_or(id).to_string()
+}
+
+/// Parses a date and time (`2026-10-24 09:00`, optionally with seconds) in the
+/// given timezone and converts it to UTC
+pub fn parse_at(s: &str, timezone: Tz) -> Result<NaiveDateTime> {
+    let local = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M"))
+        .map_err(|_| anyhow!("Invalid time {}, expected YYYY-MM-DD HH:MM", s))?;
+    let at = timezone
+        .from_local_datetime(&local)
+        .single()
+        .ok_or_else(|| anyhow!("{} doesn't happen exactly once in {}", s, timezone))?;
+    Ok(at.naive_utc())
+}
+
+/// Prints the queued posts as a table, with their times in the given timezone
+pub fn print(queued: Vec<(ScheduledPost, Track)>, timezone: Tz) {
+    if queued.is_empty() {
+        println!("No posts are queued");
+        return;
+    }
+
+    let header = ["ID", "AT", "TRACK", "URL"];
+    let rows: Vec<Vec<String>> = queued
+        .into_iter()
+        .map(|(scheduled, track)| {
+            vec![
+                scheduled.id.to_string(),
+                timezone
+                    .from_utc_datetime(&scheduled.scheduled_at)
+                    .format("%Y-%m-%d %H:%M %Z")
+                    .to_string(),
+                track.name,
+                track.url,
+            ]
+        })
+        .collect();
+    table::print(&header, &rows);
+}
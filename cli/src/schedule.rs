@@ -0,0 +1,444 @@
+use std::{str::FromStr, thread, time::Duration as StdDuration};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::Deserialize;
+
+use database::Store;
+
+/// The longest `noi run` sleeps at once, so it notices when the clock jumps
+const MAX_SLEEP_MS: i64 = 60_000;
+
+/// What happens to runs that were missed while `noi run` wasn't running
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Missed {
+    /// Runs a task once as soon as `noi run` starts if any of its runs were missed
+    CatchUp,
+    /// Waits for the next scheduled run
+    #[default]
+    Skip,
+}
+
+/// When `noi run` updates tracks and posts
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// The timezone the cron expressions are in, e.g. `Europe/Amsterdam`
+    pub timezone: String,
+    /// When to update tracks, as a cron expression
+    pub update: Option<String>,
+    /// When to post a track, as a cron expression
+    pub post: Option<String>,
+    pub missed: Missed,
+}
+
+impl ScheduleConfig {
+    pub fn timezone(&self) -> Result<Tz> {
+        self.timezone
+            .parse()
+            .map_err(|e| anyhow!("Invalid timezone {}: {}", self.timezone, e))
+    }
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            timezone: "UTC".to_string(),
+            update: None,
+            post: None,
+            missed: Missed::default(),
+        }
+    }
+}
+
+/// A task `noi run` can run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    Update,
+    Post,
+    /// Posts the track queued with `noi queue add` under this id once it's due
+    Queue(i32),
+}
+
+impl Task {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Task::Update => "update",
+            Task::Post => "post",
+            Task::Queue(_) => "queue",
+        }
+    }
+}
+
+/// A scheduled task along with when it runs next
+pub struct Job {
+    pub task: Task,
+    schedule: Schedule,
+    pub next: DateTime<Utc>,
+}
+
+/// Runs tasks on their schedules
+pub struct Scheduler {
+    timezone: Tz,
+    jobs: Vec<Job>,
+    /// Queued posts due at or before this time are left to the next post run.
+    /// `None` runs every queued post that's due
+    queue_checked: Option<DateTime<Utc>>,
+}
+
+impl Scheduler {
+    /// Works out when each scheduled task runs next. What was missed while
+    /// `noi run` wasn't running is caught up on or skipped, as configured
+    pub fn new(config: &ScheduleConfig, store: &dyn Store) -> Result<Self> {
+        let mut scheduler = Scheduler {
+            timezone: config.timezone()?,
+            jobs: Vec::new(),
+            queue_checked: None,
+        };
+
+        let mut missed = Vec::new();
+        let tasks = [(Task::Update, &config.update), (Task::Post, &config.post)];
+        for (task, expression) in tasks.iter() {
+            let expression = match expression {
+                Some(expression) => expression,
+                None => continue,
+            };
+            let schedule = parse_cron(expression)?;
+            let next = scheduler
+                .upcoming(&schedule)
+                .with_context(|| format!("The {} schedule never runs", task.as_str()))?;
+            let job = Job {
+                task: *task,
+                schedule,
+                next,
+            };
+
+            if let Some(last_run_at) = store.get_last_run(task.as_str())? {
+                if scheduler.missed_run(&job, last_run_at) {
+                    missed.push((*task, last_run_at));
+                }
+            }
+            scheduler.jobs.push(job);
+        }
+        scheduler.handle_missed(config.missed, store, &missed)?;
+
+        Ok(scheduler)
+    }
+
+    /// Decides what happens to the runs of `missed`, each along with when the
+    /// task last ran, and to the queued posts that came due while `noi run`
+    /// wasn't running. Catching up runs each task once and posts the queued
+    /// posts right away, while skipping waits for the next scheduled run and
+    /// takes the queued posts off the queue
+    fn handle_missed(
+        &mut self,
+        policy: Missed,
+        store: &dyn Store,
+        missed: &[(Task, NaiveDateTime)],
+    ) -> Result<()> {
+        let now = Utc::now();
+        for (task, last_run_at) in missed.iter() {
+            let since = self.local(Utc.from_utc_datetime(last_run_at));
+            match policy {
+                Missed::CatchUp => {
+                    self.log(&format!(
+                        "Missed a {} run since {}, catching up",
+                        task.as_str(),
+                        since
+                    ));
+                    if let Some(job) = self.jobs.iter_mut().find(|job| job.task == *task) {
+                        job.next = now;
+                    }
+                }
+                Missed::Skip => self.log(&format!(
+                    "Missed a {} run since {}, skipping it",
+                    task.as_str(),
+                    since
+                )),
+            }
+        }
+
+        for (scheduled, track) in store.get_scheduled_posts()? {
+            let due = Utc.from_utc_datetime(&scheduled.scheduled_at);
+            // Queued tracks that have already been posted are left to the
+            // next post run, which skips them
+            if due > now || store.is_track_posted(track.id)? {
+                continue;
+            }
+            match policy {
+                Missed::CatchUp => self.log(&format!(
+                    "Missed queued post {} of {} at {}, catching up",
+                    scheduled.id,
+                    track.name,
+                    self.local(due)
+                )),
+                Missed::Skip => {
+                    self.log(&format!(
+                        "Missed queued post {} of {} at {}, skipping it",
+                        scheduled.id,
+                        track.name,
+                        self.local(due)
+                    ));
+                    store.delete_scheduled_post(scheduled.id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Sleeps until the next task or queued post is due and returns its task.
+    /// The queue is checked again every time it wakes up, so posts queued while
+    /// `noi run` is running are picked up
+    pub fn wait(&self, store: &dyn Store) -> Result<Task> {
+        loop {
+            let job = self.jobs.iter().min_by_key(|job| job.next);
+            let next = match (job, self.queued(store)?) {
+                (Some(job), Some((id, queued))) if queued < job.next => {
+                    Some((queued, Task::Queue(id)))
+                }
+                (Some(job), _) => Some((job.next, job.task)),
+                (None, queued) => queued.map(|(id, queued)| (queued, Task::Queue(id))),
+            };
+
+            let millis = match next {
+                Some((due, task)) => {
+                    let left = due - Utc::now();
+                    if left <= chrono::Duration::zero() {
+                        return Ok(task);
+                    }
+                    left.num_milliseconds().clamp(1, MAX_SLEEP_MS)
+                }
+                None => MAX_SLEEP_MS,
+            };
+            thread::sleep(StdDuration::from_millis(millis as u64));
+        }
+    }
+
+    /// The id of the next queued post `noi run` posts by itself along with
+    /// when it's due. Queued tracks that have already been posted are left to
+    /// the next post run, which skips them
+    pub fn queued(&self, store: &dyn Store) -> Result<Option<(i32, DateTime<Utc>)>> {
+        for (scheduled, track) in store.get_scheduled_posts()? {
+            let at = Utc.from_utc_datetime(&scheduled.scheduled_at);
+            if self.queue_checked.is_none_or(|checked| at > checked)
+                && !store.is_track_posted(track.id)?
+            {
+                return Ok(Some((scheduled.id, at)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Records that a task ran and works out when it runs next. A queued post
+    /// that failed is left to the next post run instead of being retried right away
+    pub fn ran(
+        &mut self,
+        task: Task,
+        store: &dyn Store,
+        started_at: DateTime<Utc>,
+        succeeded: bool,
+    ) -> Result<()> {
+        store.set_last_run(task.as_str(), started_at.naive_utc())?;
+        if matches!(task, Task::Queue(_)) && !succeeded {
+            self.queue_checked = Some(Utc::now());
+        }
+
+        let timezone = self.timezone;
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.task == task) {
+            // Runs that came due while the task was running are skipped
+            let now = Utc::now().with_timezone(&timezone);
+            if let Some(next) = job.schedule.after(&now).next() {
+                job.next = next.with_timezone(&Utc);
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints a message prefixed with the local time
+    pub fn log(&self, message: &str) {
+        println!("[{}] {}", self.local(Utc::now()), message);
+    }
+
+    /// Formats a time in the configured timezone
+    pub fn local(&self, time: DateTime<Utc>) -> String {
+        time.with_timezone(&self.timezone)
+            .format("%Y-%m-%d %H:%M:%S %Z")
+            .to_string()
+    }
+
+    /// The next time a schedule is due from now
+    fn upcoming(&self, schedule: &Schedule) -> Option<DateTime<Utc>> {
+        let now = Utc::now().with_timezone(&self.timezone);
+        schedule
+            .after(&now)
+            .next()
+            .map(|next| next.with_timezone(&Utc))
+    }
+
+    /// Whether a task was due to run at some point between its last run and now
+    fn missed_run(&self, job: &Job, last_run_at: NaiveDateTime) -> bool {
+        let last_run_at = self.timezone.from_utc_datetime(&last_run_at);
+        job.schedule
+            .after(&last_run_at)
+            .next()
+            .is_some_and(|due| due.with_timezone(&Utc) <= Utc::now())
+    }
+}
+
+/// Parses a cron expression. Besides the usual five fields, the six or seven
+/// fields of the `cron` crate are accepted, which add seconds and years
+pub fn parse_cron(expression: &str) -> Result<Schedule> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let converted = match fields.as_slice() {
+        [minute, hour, day, month, weekday] => format!(
+            "0 {} {} {} {} {}",
+            minute,
+            hour,
+            day,
+            month,
+            crate_weekdays(weekday)
+        ),
+        _ => expression.to_string(),
+    };
+
+    Schedule::from_str(&converted)
+        .map_err(|e| anyhow!("Invalid cron expression {}: {}", expression, e))
+}
+
+/// Converts the days of the week of a five field cron expression, where Sunday
+/// is 0 or 7, to the `cron` crate's, where Sunday is 1 and Saturday is 7
+fn crate_weekdays(field: &str) -> String {
+    field
+        .split(',')
+        .map(crate_weekday_item)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Converts a day, a range of days or either with a step. Names and anything
+/// that isn't a day are left as is for the `cron` crate to check
+fn crate_weekday_item(item: &str) -> String {
+    let (days, step) = match item.split_once('/') {
+        Some((days, step)) => (days, Some(step)),
+        None => (item, None),
+    };
+    let day = |s: &str| s.parse::<u32>().ok().filter(|day| *day <= 7);
+    let (start, end) = match days.split_once('-') {
+        Some((start, end)) => match (day(start), day(end)) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return item.to_string(),
+        },
+        None => match day(days) {
+            Some(day) => (day, day),
+            None => return item.to_string(),
+        },
+    };
+    let with_step = |days: String| match step {
+        Some(step) => format!("{}/{}", days, step),
+        None => days,
+    };
+
+    if start == end && !days.contains('-') {
+        with_step(crate_weekday(start).to_string())
+    } else if start == 0 || end < 7 {
+        with_step(format!("{}-{}", start + 1, end.min(6) + 1))
+    } else {
+        // Sunday ends the range as 7 but starts the week for the crate, so the
+        // range wraps around and is written out day by day instead
+        let step = match step.map(str::parse::<usize>) {
+            Some(Ok(step)) if step > 0 => step,
+            Some(_) => return item.to_string(),
+            None => 1,
+        };
+        (start..=end)
+            .step_by(step)
+            .map(|day| crate_weekday(day).to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+fn crate_weekday(day: u32) -> u32 {
+    day % 7 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+    use super::*;
+
+    /// The days of the week a schedule posting at 9:00 on `weekdays` runs on,
+    /// from Sunday to Saturday
+    fn runs_on(weekdays: &str) -> Vec<Weekday> {
+        let schedule = parse_cron(&format!("0 9 * * {}", weekdays)).unwrap();
+        // A Sunday
+        let start = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|start| Utc.from_utc_datetime(&start))
+            .unwrap();
+        schedule
+            .after(&start)
+            .take_while(|at| *at < start + Duration::days(7))
+            .map(|at| at.weekday())
+            .collect()
+    }
+
+    #[test]
+    fn parses_every_day_and_steps() {
+        use Weekday::*;
+        assert_eq!(runs_on("*"), vec![Sun, Mon, Tue, Wed, Thu, Fri, Sat]);
+        assert_eq!(runs_on("*/2"), vec![Sun, Tue, Thu, Sat]);
+        assert_eq!(runs_on("1-5/2"), vec![Mon, Wed, Fri]);
+    }
+
+    #[test]
+    fn parses_ranges() {
+        use Weekday::*;
+        assert_eq!(runs_on("1-5"), vec![Mon, Tue, Wed, Thu, Fri]);
+        assert_eq!(runs_on("0-2"), vec![Sun, Mon, Tue]);
+        assert_eq!(runs_on("MON-FRI"), vec![Mon, Tue, Wed, Thu, Fri]);
+    }
+
+    #[test]
+    fn parses_ranges_ending_on_sunday() {
+        use Weekday::*;
+        assert_eq!(runs_on("5-7"), vec![Sun, Fri, Sat]);
+        assert_eq!(runs_on("1-7"), vec![Sun, Mon, Tue, Wed, Thu, Fri, Sat]);
+        assert_eq!(runs_on("0-7"), vec![Sun, Mon, Tue, Wed, Thu, Fri, Sat]);
+        assert_eq!(runs_on("1-7/2"), vec![Sun, Mon, Wed, Fri]);
+    }
+
+    #[test]
+    fn parses_sunday_as_0_or_7() {
+        use Weekday::*;
+        assert_eq!(runs_on("0"), vec![Sun]);
+        assert_eq!(runs_on("7"), vec![Sun]);
+        assert_eq!(runs_on("6,0"), vec![Sun, Sat]);
+    }
+
+    #[test]
+    fn parses_the_fields_of_the_cron_crate() {
+        let schedule = parse_cron("0 30 9 * * Mon *").unwrap();
+
+        assert!(schedule.upcoming(Utc).next().is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in ["0 9 * * 8", "0 9 * * 5-2", "0 9 * *", "every day"].iter() {
+            assert!(parse_cron(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,705 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Duration, NaiveDateTime, Utc};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use serde::{de, Deserialize, Deserializer};
+
+use database::{
+    models::{Playlist, PlaylistEntry, Track},
+    Store,
+};
+
+use crate::table;
+
+/// The ways the track to post can be picked
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Picks a playlist by weight and then one of its tracks at random
+    #[default]
+    Weighted,
+    /// Picks any track at random, so larger playlists are picked more often
+    Uniform,
+    /// Takes turns between playlists, picking one of the tracks at random
+    RoundRobin,
+    /// Picks the track that was added to its playlist the longest ago
+    OldestAdded,
+}
+
+impl Strategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Strategy::Weighted => "weighted",
+            Strategy::Uniform => "uniform",
+            Strategy::RoundRobin => "round-robin",
+            Strategy::OldestAdded => "oldest-added",
+        }
+    }
+}
+
+/// What happens once every track has been posted
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Exhaustion {
+    /// Fails with an error saying there's nothing left to post
+    #[default]
+    Error,
+    /// Puts the tracks that were last posted longer than `recycle_after` ago
+    /// back in the pool
+    Recycle,
+    /// Puts every posted track of `reset_playlist`, or of every playlist when
+    /// it's unset, back in the pool
+    Reset,
+}
+
+/// A length of time in hours, days or weeks, like `12h`, `180d` or `2w`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Age(pub Duration);
+
+impl FromStr for Age {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid age {}, expected e.g. 12h, 180d or 2w", s);
+        let unit = s.chars().last().ok_or_else(invalid)?;
+        let count: u32 = s[..s.len() - unit.len_utf8()]
+            .parse()
+            .map_err(|_| invalid())?;
+        let hours_per_unit = match unit {
+            'h' => 1,
+            'd' => 24,
+            'w' => 24 * 7,
+            _ => return Err(invalid()),
+        };
+        let hours = i64::from(count)
+            .checked_mul(hours_per_unit)
+            .ok_or_else(invalid)?;
+        Ok(Age(Duration::hours(hours)))
+    }
+}
+
+impl Age {
+    /// The time this long ago
+    pub fn ago(&self) -> Result<NaiveDateTime> {
+        Utc::now()
+            .naive_utc()
+            .checked_sub_signed(self.0)
+            .ok_or_else(|| anyhow!("{} ago is further back than dates go", self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Age {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hours = self.0.num_hours();
+        if hours % 24 == 0 {
+            write!(f, "{}d", hours / 24)
+        } else {
+            write!(f, "{}h", hours)
+        }
+    }
+}
+
+/// How the track to post is picked
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SelectionConfig {
+    pub strategy: Strategy,
+    /// Skips tracks by an artist in any of this many of the most recent posts
+    pub artist_cooldown: usize,
+    /// Skips tracks from an album in any of this many of the most recent posts
+    pub album_cooldown: usize,
+    /// Seeds the random picks so they can be reproduced. Picks are random on
+    /// every run when unset
+    pub seed: Option<u64>,
+    /// What happens once every track has been posted
+    pub exhaustion: Exhaustion,
+    /// How long ago a track must have been posted before it's recycled
+    pub recycle_after: Age,
+    /// The playlist, by Spotify id or name, whose tracks are reset
+    pub reset_playlist: Option<String>,
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        SelectionConfig {
+            strategy: Strategy::default(),
+            artist_cooldown: 0,
+            album_cooldown: 0,
+            seed: None,
+            exhaustion: Exhaustion::default(),
+            recycle_after: Age(Duration::days(180)),
+            reset_playlist: None,
+        }
+    }
+}
+
+impl SelectionConfig {
+    /// Builds the configured strategy along with what it needs to know about
+    /// past posts
+    pub fn build(&self, store: &dyn Store) -> Result<Box<dyn SelectionStrategy>> {
+        let strategy: Box<dyn SelectionStrategy> = match self.strategy {
+            Strategy::Weighted => Box::new(Weighted),
+            Strategy::Uniform => Box::new(Uniform),
+            Strategy::RoundRobin => Box::new(RoundRobin {
+                last_playlist_id: store.get_last_post()?.and_then(|post| post.playlist_id),
+            }),
+            Strategy::OldestAdded => Box::new(OldestAdded),
+        };
+        if self.artist_cooldown == 0 && self.album_cooldown == 0 {
+            return Ok(strategy);
+        }
+
+        let mut recent = Vec::new();
+        for track in store
+            .get_recently_posted_tracks(self.artist_cooldown.max(self.album_cooldown))?
+            .iter()
+        {
+            let details = store.get_track_details(track)?;
+            let artist_ids = details.artists.iter().map(|artist| artist.id).collect();
+            recent.push((artist_ids, track.album_id));
+        }
+
+        Ok(Box::new(Cooldown {
+            inner: strategy,
+            artist_cooldown: self.artist_cooldown,
+            album_cooldown: self.album_cooldown,
+            recent,
+        }))
+    }
+
+    /// Puts posted tracks back in the pool once every track has been posted,
+    /// as far as the exhaustion policy allows
+    pub fn refill(&self, store: &dyn Store) -> Result<()> {
+        let nothing_left =
+            "There are no tracks left to post. Run `noi tracks update` to fetch new ones";
+        match self.exhaustion {
+            Exhaustion::Error => bail!(
+                "{} or `noi tracks reset` to post tracks again",
+                nothing_left
+            ),
+            Exhaustion::Recycle => {
+                let posted_before = self.recycle_after.ago()?;
+                let recycled = store.reset_posted_tracks(None, Some(posted_before))?;
+                if recycled == 0 {
+                    bail!(
+                        "{}. No track was last posted more than {} ago, so none can be recycled",
+                        nothing_left,
+                        self.recycle_after
+                    );
+                }
+                println!(
+                    "Every track has been posted, recycled {} tracks last posted more than {} ago",
+                    recycled, self.recycle_after
+                );
+            }
+            Exhaustion::Reset => {
+                let playlist = match &self.reset_playlist {
+                    Some(playlist) => Some(
+                        store
+                            .get_playlist(playlist)?
+                            .with_context(|| format!("No playlist found matching {}", playlist))?,
+                    ),
+                    None => None,
+                };
+                let reset = store.reset_posted_tracks(playlist.as_ref().map(|p| p.id), None)?;
+                if reset == 0 {
+                    bail!("{}. There are no posted tracks to reset", nothing_left);
+                }
+                match playlist {
+                    Some(playlist) => println!(
+                        "Every track has been posted, reset {} tracks of {}",
+                        reset, playlist.name
+                    ),
+                    None => println!("Every track has been posted, reset {} tracks", reset),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A playlist along with the tracks in it that can be picked
+pub type Group<'a> = (&'a Playlist, Vec<&'a PlaylistEntry>);
+
+/// Borrows the tracks left to post so strategies can narrow them down
+pub fn pool(tracks_by_playlist: &[(Playlist, Vec<PlaylistEntry>)]) -> Vec<Group<'_>> {
+    tracks_by_playlist
+        .iter()
+        .map(|(playlist, entries)| (playlist, entries.iter().collect()))
+        .collect()
+}
+
+/// The chance of a track being picked through one of its playlists
+#[derive(Clone, Copy)]
+pub struct Odds<'a> {
+    pub playlist: &'a Playlist,
+    pub entry: &'a PlaylistEntry,
+    pub chance: f64,
+}
+
+/// A way of picking the track to post
+pub trait SelectionStrategy {
+    /// How the strategy is recorded alongside the posts it picks
+    fn name(&self) -> String;
+
+    /// Works out the chance of each track in the pool being picked. Tracks
+    /// that can't be picked are left out
+    fn odds<'a>(&self, pool: &[Group<'a>]) -> Vec<Odds<'a>>;
+
+    /// Updates the strategy as if a track was posted, so the next picks can be
+    /// worked out without posting. Queued posts weren't picked from a playlist
+    fn picked(&mut self, _playlist: Option<&Playlist>, _entry: &PlaylistEntry) {}
+}
+
+/// The playlists that can be picked from. Playlists with a weight of 0 are
+/// never picked, whatever the strategy
+fn eligible<'p, 'a>(pool: &'p [Group<'a>]) -> impl Iterator<Item = &'p Group<'a>> {
+    pool.iter()
+        .filter(|(playlist, entries)| playlist.weight > 0 && !entries.is_empty())
+}
+
+/// Splits a chance evenly between the tracks of a playlist
+fn spread<'a>(playlist: &'a Playlist, entries: &[&'a PlaylistEntry], chance: f64) -> Vec<Odds<'a>> {
+    let chance = chance / entries.len() as f64;
+    entries
+        .iter()
+        .map(|entry| Odds {
+            playlist,
+            entry,
+            chance,
+        })
+        .collect()
+}
+
+pub struct Weighted;
+
+impl SelectionStrategy for Weighted {
+    fn name(&self) -> String {
+        Strategy::Weighted.as_str().to_string()
+    }
+
+    fn odds<'a>(&self, pool: &[Group<'a>]) -> Vec<Odds<'a>> {
+        let total: i64 = eligible(pool)
+            .map(|(playlist, _)| i64::from(playlist.weight))
+            .sum();
+        eligible(pool)
+            .flat_map(|(playlist, entries)| {
+                spread(playlist, entries, f64::from(playlist.weight) / total as f64)
+            })
+            .collect()
+    }
+}
+
+pub struct Uniform;
+
+impl SelectionStrategy for Uniform {
+    fn name(&self) -> String {
+        Strategy::Uniform.as_str().to_string()
+    }
+
+    fn odds<'a>(&self, pool: &[Group<'a>]) -> Vec<Odds<'a>> {
+        let entries: Vec<(&Playlist, &PlaylistEntry)> = eligible(pool)
+            .flat_map(|(playlist, entries)| entries.iter().map(move |entry| (*playlist, *entry)))
+            .collect();
+        let mut listings: HashMap<i32, usize> = HashMap::new();
+        for (_, entry) in entries.iter() {
+            *listings.entry(entry.track.id).or_default() += 1;
+        }
+
+        // A track in several playlists is as likely as any other, so its
+        // chance is split between them
+        entries
+            .iter()
+            .map(|(playlist, entry)| Odds {
+                playlist,
+                entry,
+                chance: 1.0 / (listings.len() * listings[&entry.track.id]) as f64,
+            })
+            .collect()
+    }
+}
+
+pub struct RoundRobin {
+    /// The playlist the most recent post was picked from
+    pub last_playlist_id: Option<i32>,
+}
+
+impl SelectionStrategy for RoundRobin {
+    fn name(&self) -> String {
+        Strategy::RoundRobin.as_str().to_string()
+    }
+
+    fn odds<'a>(&self, pool: &[Group<'a>]) -> Vec<Odds<'a>> {
+        // Playlists take turns in the order they were added
+        let mut groups: Vec<&Group> = eligible(pool).collect();
+        groups.sort_by_key(|(playlist, _)| playlist.id);
+        let next = groups
+            .iter()
+            .find(|(playlist, _)| Some(playlist.id) > self.last_playlist_id)
+            .or_else(|| groups.first());
+
+        match next {
+            Some((playlist, entries)) => spread(playlist, entries, 1.0),
+            None => Vec::new(),
+        }
+    }
+
+    fn picked(&mut self, playlist: Option<&Playlist>, _entry: &PlaylistEntry) {
+        self.last_playlist_id = playlist.map(|p| p.id);
+    }
+}
+
+pub struct OldestAdded;
+
+impl SelectionStrategy for OldestAdded {
+    fn name(&self) -> String {
+        Strategy::OldestAdded.as_str().to_string()
+    }
+
+    fn odds<'a>(&self, pool: &[Group<'a>]) -> Vec<Odds<'a>> {
+        // Tracks that were added before `added_at` was recorded have none and
+        // count as the oldest
+        let oldest = eligible(pool)
+            .flat_map(|(_, entries)| entries.iter().map(|entry| entry.added_at))
+            .min();
+        let entries: Vec<(&Playlist, &PlaylistEntry)> = eligible(pool)
+            .flat_map(|(playlist, entries)| entries.iter().map(move |entry| (*playlist, *entry)))
+            .filter(|(_, entry)| Some(entry.added_at) == oldest)
+            .collect();
+
+        let chance = 1.0 / entries.len() as f64;
+        entries
+            .into_iter()
+            .map(|(playlist, entry)| Odds {
+                playlist,
+                entry,
+                chance,
+            })
+            .collect()
+    }
+}
+
+/// Leaves out tracks by the artists or from the albums of the most recent
+/// posts before another strategy picks from what's left
+pub struct Cooldown {
+    pub inner: Box<dyn SelectionStrategy>,
+    pub artist_cooldown: usize,
+    pub album_cooldown: usize,
+    /// The artists and album of the most recently posted tracks, most recent
+    /// first
+    pub recent: Vec<(Vec<i32>, Option<i32>)>,
+}
+
+impl Cooldown {
+    fn cooling_down(&self, entry: &PlaylistEntry) -> bool {
+        let artist = self
+            .recent
+            .iter()
+            .take(self.artist_cooldown)
+            .any(|(artist_ids, _)| entry.artist_ids.iter().any(|id| artist_ids.contains(id)));
+        let album = entry.track.album_id.is_some_and(|id| {
+            self.recent
+                .iter()
+                .take(self.album_cooldown)
+                .any(|(_, album_id)| *album_id == Some(id))
+        });
+        artist || album
+    }
+}
+
+impl SelectionStrategy for Cooldown {
+    fn name(&self) -> String {
+        let mut name = self.inner.name();
+        if self.artist_cooldown > 0 {
+            name.push_str(&format!("+artist-cooldown={}", self.artist_cooldown));
+        }
+        if self.album_cooldown > 0 {
+            name.push_str(&format!("+album-cooldown={}", self.album_cooldown));
+        }
+        name
+    }
+
+    fn odds<'a>(&self, pool: &[Group<'a>]) -> Vec<Odds<'a>> {
+        let cooled: Vec<Group> = pool
+            .iter()
+            .map(|(playlist, entries)| {
+                let entries = entries
+                    .iter()
+                    .copied()
+                    .filter(|entry| !self.cooling_down(entry))
+                    .collect();
+                (*playlist, entries)
+            })
+            .collect();
+
+        // A post is better than none, so once every track left is cooling
+        // down they can all be picked again
+        let odds = self.inner.odds(&cooled);
+        if odds.is_empty() {
+            self.inner.odds(pool)
+        } else {
+            odds
+        }
+    }
+
+    fn picked(&mut self, playlist: Option<&Playlist>, entry: &PlaylistEntry) {
+        self.inner.picked(playlist, entry);
+        self.recent
+            .insert(0, (entry.artist_ids.clone(), entry.track.album_id));
+    }
+}
+
+/// The random number generator a pick from the pool is made with. The seed is
+/// mixed with how many tracks are left, so the same seed always gives the same
+/// pick from the same tracks while each post makes a new draw
+pub fn rng(seed: Option<u64>, pool: &[Group]) -> StdRng {
+    let left: HashSet<i32> = pool
+        .iter()
+        .flat_map(|(_, entries)| entries.iter().map(|entry| entry.track.id))
+        .collect();
+    match seed {
+        // Spreads the count over every bit so nearby seeds don't share draws
+        Some(seed) => {
+            StdRng::seed_from_u64(seed ^ (left.len() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        }
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Picks a track using the odds of a strategy. Returns `None` when there's
+/// no track that can be picked
+pub fn pick<'o, 'a, R: Rng>(odds: &'o [Odds<'a>], rng: &mut R) -> Option<&'o Odds<'a>> {
+    let index = WeightedIndex::new(odds.iter().map(|o| o.chance)).ok()?;
+    Some(&odds[index.sample(rng)])
+}
+
+/// Works out the next `count` picks without posting anything. Each picked
+/// track is treated as posted before the next pick, so it's not picked again.
+/// Each pick is drawn from the seed and the tracks left, like `noi tracks post`
+pub fn simulate<'a>(
+    strategy: &mut dyn SelectionStrategy,
+    mut pool: Vec<Group<'a>>,
+    count: usize,
+    seed: Option<u64>,
+) -> Vec<Odds<'a>> {
+    let mut picks = Vec::new();
+    for _ in 0..count {
+        let odds = strategy.odds(&pool);
+        let picked = match pick(&odds, &mut rng(seed, &pool)) {
+            Some(picked) => *picked,
+            None => break,
+        };
+        let chance = track_chance(&odds, &picked.entry.track);
+
+        strategy.picked(Some(picked.playlist), picked.entry);
+        for (_, entries) in pool.iter_mut() {
+            entries.retain(|entry| entry.track.id != picked.entry.track.id);
+        }
+        picks.push(Odds { chance, ..picked });
+    }
+    picks
+}
+
+/// The chance of a track being picked through any of the playlists it's in
+pub fn track_chance(odds: &[Odds], track: &Track) -> f64 {
+    odds.iter()
+        .filter(|o| o.entry.track.id == track.id)
+        .fold(0.0, |sum, o| sum + o.chance)
+}
+
+/// Prints the chance of a track being picked from each playlist
+pub fn explain(pool: &[Group], odds: &[Odds]) {
+    let header = ["PLAYLIST", "WEIGHT", "TRACKS", "CHANCE"];
+    let rows: Vec<Vec<String>> = pool
+        .iter()
+        .map(|(playlist, entries)| {
+            // Summing with `sum` would give -0 for playlists with no chance
+            let chance = odds
+                .iter()
+                .filter(|o| o.playlist.id == playlist.id)
+                .fold(0.0, |sum, o| sum + o.chance);
+            vec![
+                playlist.name.clone(),
+                playlist.weight.to_string(),
+                entries.len().to_string(),
+                percent(chance),
+            ]
+        })
+        .collect();
+
+    table::print(&header, &rows);
+}
+
+pub fn percent(chance: f64) -> String {
+    format!("{:.2}%", chance * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three playlists of four tracks each, the second twice as likely to be
+    /// picked by weight. Each track's artist and album is shared with the one
+    /// after it
+    fn tracks_by_playlist() -> Vec<(Playlist, Vec<PlaylistEntry>)> {
+        (1..=3)
+            .map(|playlist_id| {
+                let playlist = Playlist {
+                    id: playlist_id,
+                    spotify_id: format!("playlist-{}", playlist_id),
+                    name: format!("Playlist {}", playlist_id),
+                    weight: if playlist_id == 2 { 2 } else { 1 },
+                };
+                let entries = (1..=4)
+                    .map(|n| {
+                        let id = playlist_id * 10 + n;
+                        PlaylistEntry {
+                            track: Track {
+                                id,
+                                spotify_id: format!("track-{}", id),
+                                name: format!("Track {}", id),
+                                url: format!("https://open.spotify.com/track/track-{}", id),
+                                album_id: Some(id / 2),
+                                duration_ms: None,
+                                explicit: None,
+                                popularity: None,
+                                isrc: None,
+                            },
+                            added_at: None,
+                            artist_ids: vec![id / 2],
+                        }
+                    })
+                    .collect();
+                (playlist, entries)
+            })
+            .collect()
+    }
+
+    fn strategies() -> Vec<Box<dyn SelectionStrategy>> {
+        vec![
+            Box::new(Weighted),
+            Box::new(Uniform),
+            Box::new(RoundRobin {
+                last_playlist_id: None,
+            }),
+            Box::new(OldestAdded),
+            Box::new(Cooldown {
+                inner: Box::new(Weighted),
+                artist_cooldown: 1,
+                album_cooldown: 1,
+                recent: Vec::new(),
+            }),
+        ]
+    }
+
+    fn simulated_ids(strategy: &mut dyn SelectionStrategy, seed: u64) -> Vec<i32> {
+        let tracks_by_playlist = tracks_by_playlist();
+        simulate(strategy, pool(&tracks_by_playlist), 8, Some(seed))
+            .iter()
+            .map(|picked| picked.entry.track.id)
+            .collect()
+    }
+
+    #[test]
+    fn parses_ages() {
+        assert_eq!("12h".parse::<Age>().unwrap(), Age(Duration::hours(12)));
+        assert_eq!("180d".parse::<Age>().unwrap(), Age(Duration::days(180)));
+        assert_eq!("2w".parse::<Age>().unwrap(), Age(Duration::weeks(2)));
+        for invalid in ["", "d", "12", "-1d", "1.5d", "12m"].iter() {
+            assert!(invalid.parse::<Age>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn rejects_an_age_further_back_than_dates_go() {
+        let age: Age = "4294967295w".parse().unwrap();
+
+        assert!(age.ago().is_err());
+        assert!("180d".parse::<Age>().unwrap().ago().is_ok());
+    }
+
+    #[test]
+    fn picks_the_same_track_from_the_same_seed() {
+        let tracks_by_playlist = tracks_by_playlist();
+        let pool = pool(&tracks_by_playlist);
+        for strategy in strategies() {
+            let odds = strategy.odds(&pool);
+            let first = pick(&odds, &mut rng(Some(42), &pool))
+                .unwrap()
+                .entry
+                .track
+                .id;
+            for _ in 0..10 {
+                let picked = pick(&odds, &mut rng(Some(42), &pool)).unwrap();
+                assert_eq!(picked.entry.track.id, first, "{}", strategy.name());
+            }
+        }
+    }
+
+    #[test]
+    fn draws_again_from_the_same_seed_once_a_track_is_posted() {
+        let tracks_by_playlist = tracks_by_playlist();
+        let mut pool = pool(&tracks_by_playlist);
+        let first = rng(Some(42), &pool).gen::<u64>();
+        pool[0].1.remove(0);
+
+        assert_ne!(rng(Some(42), &pool).gen::<u64>(), first);
+    }
+
+    #[test]
+    fn simulates_the_same_picks_from_the_same_seed() {
+        for (mut strategy, mut again) in strategies().into_iter().zip(strategies()) {
+            let picks = simulated_ids(strategy.as_mut(), 42);
+
+            assert_eq!(
+                picks,
+                simulated_ids(again.as_mut(), 42),
+                "{}",
+                strategy.name()
+            );
+            let unique: HashSet<&i32> = picks.iter().collect();
+            assert_eq!(unique.len(), picks.len(), "{}", strategy.name());
+        }
+    }
+
+    #[test]
+    fn simulates_each_pick_from_the_seed_like_a_post() {
+        let tracks_by_playlist = tracks_by_playlist();
+        for mut strategy in strategies() {
+            let pool = pool(&tracks_by_playlist);
+            let odds = strategy.odds(&pool);
+            let posted = pick(&odds, &mut rng(Some(7), &pool))
+                .unwrap()
+                .entry
+                .track
+                .id;
+
+            let picks = simulated_ids(strategy.as_mut(), 7);
+            assert_eq!(picks[0], posted, "{}", strategy.name());
+        }
+    }
+
+    #[test]
+    fn stops_simulating_once_the_pool_is_empty() {
+        let tracks_by_playlist = tracks_by_playlist();
+        let picks = simulate(&mut Uniform, pool(&tracks_by_playlist), 20, Some(1));
+
+        assert_eq!(picks.len(), 12);
+    }
+}
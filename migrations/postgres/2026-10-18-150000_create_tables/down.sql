@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE posts;
+DROP TABLE playlist_tracks;
+DROP TABLE track_artists;
+DROP TABLE tracks;
+DROP TABLE artists;
+DROP TABLE albums;
+DROP TABLE playlist_sync;
+DROP TABLE playlists
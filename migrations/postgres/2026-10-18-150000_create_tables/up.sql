@@ -0,0 +1,74 @@
+-- Your SQL goes here
+-- Postgres databases start from the schema SQLite databases reach after all
+-- of their migrations, so this has the same version as the last of them
+CREATE TABLE playlists (
+    id SERIAL PRIMARY KEY,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE playlist_sync (
+    playlist_id INTEGER PRIMARY KEY REFERENCES playlists (id) ON DELETE CASCADE,
+    snapshot_id TEXT NOT NULL,
+    -- When the most recently added track we've seen was added to the playlist
+    last_added_at TIMESTAMP,
+    -- Timestamps are stored in UTC like SQLite's CURRENT_TIMESTAMP
+    synced_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE albums (
+    id SERIAL PRIMARY KEY,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    -- Spotify gives the year, year and month or full date depending on what's known
+    release_date TEXT,
+    image_url TEXT,
+    thumbnail_url TEXT
+);
+
+CREATE TABLE artists (
+    id SERIAL PRIMARY KEY,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE tracks (
+    id SERIAL PRIMARY KEY,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    album_id INTEGER REFERENCES albums (id) ON DELETE SET NULL,
+    duration_ms INTEGER,
+    explicit BOOLEAN,
+    popularity INTEGER,
+    isrc TEXT
+);
+
+CREATE TABLE track_artists (
+    track_id INTEGER NOT NULL REFERENCES tracks (id) ON DELETE CASCADE,
+    artist_id INTEGER NOT NULL REFERENCES artists (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (track_id, artist_id)
+);
+
+CREATE TABLE playlist_tracks (
+    playlist_id INTEGER NOT NULL REFERENCES playlists (id) ON DELETE CASCADE,
+    track_id INTEGER NOT NULL REFERENCES tracks (id) ON DELETE CASCADE,
+    added_at TIMESTAMP,
+    -- The Spotify id of the user who added the track
+    added_by TEXT,
+    -- Set when the track is no longer in the Spotify playlist
+    removed_at TIMESTAMP,
+    PRIMARY KEY (playlist_id, track_id)
+);
+
+CREATE TABLE posts (
+    id SERIAL PRIMARY KEY,
+    track_id INTEGER NOT NULL REFERENCES tracks (id) ON DELETE CASCADE,
+    destination TEXT NOT NULL,
+    remote_id TEXT,
+    remote_url TEXT,
+    -- Nullable like in SQLite, where posts made before posts were recorded have none
+    posted_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc'),
+    status TEXT NOT NULL
+)
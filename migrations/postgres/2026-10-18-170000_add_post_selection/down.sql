@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE posts DROP COLUMN strategy;
+ALTER TABLE posts DROP COLUMN playlist_id
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- The playlist the track was picked from and the strategy that picked it
+ALTER TABLE posts ADD COLUMN playlist_id INTEGER REFERENCES playlists (id) ON DELETE SET NULL;
+ALTER TABLE posts ADD COLUMN strategy TEXT
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Tracks queued with `noi queue add` to be posted at a set time
+CREATE TABLE scheduled_posts (
+    id SERIAL PRIMARY KEY,
+    track_id INTEGER NOT NULL REFERENCES tracks (id) ON DELETE CASCADE,
+    -- When the track is due to be posted, in UTC
+    scheduled_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc')
+)
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Undoing a post adds a post with the `undone` status that points at the post it
+-- undid, so the history shows both. A post that was undone no longer keeps its
+-- track out of the pool
+ALTER TABLE posts ADD COLUMN undone_post_id INTEGER REFERENCES posts (id) ON DELETE CASCADE
@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE tracks_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    playlist_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    posted INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+INSERT INTO tracks_old (id, spotify_id, playlist_id, name, url, posted)
+SELECT id, spotify_id, playlist_id, name, url, posted FROM tracks
+WHERE playlist_id IS NOT NULL;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_old RENAME TO tracks
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- Posted tracks outlive the playlist they came from, so `playlist_id` becomes
+-- nullable. SQLite can't alter a column in place so the table is rebuilt.
+CREATE TABLE tracks_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    playlist_id INTEGER,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    posted INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE SET NULL
+);
+
+INSERT INTO tracks_new (id, spotify_id, playlist_id, name, url, posted)
+SELECT id, spotify_id, playlist_id, name, url, posted FROM tracks;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_new RENAME TO tracks
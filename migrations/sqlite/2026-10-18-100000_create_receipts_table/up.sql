@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE receipts (
+    id INTEGER PRIMARY KEY NOT NULL,
+    track_id INTEGER NOT NULL,
+    destination TEXT NOT NULL,
+    remote_id TEXT,
+    remote_url TEXT,
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+)
@@ -0,0 +1,40 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE tracks_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    playlist_id INTEGER,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    posted INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE SET NULL
+);
+
+INSERT INTO tracks_old (id, spotify_id, playlist_id, name, url, posted)
+SELECT id, spotify_id, playlist_id, name, url,
+    EXISTS (SELECT 1 FROM posts WHERE posts.track_id = tracks.id AND posts.status = 'posted')
+FROM tracks;
+
+CREATE TABLE receipts (
+    id INTEGER PRIMARY KEY NOT NULL,
+    track_id INTEGER NOT NULL,
+    destination TEXT NOT NULL,
+    remote_id TEXT,
+    remote_url TEXT,
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+INSERT INTO receipts (track_id, destination, remote_id, remote_url)
+SELECT track_id, destination, remote_id, remote_url FROM posts
+WHERE status != 'failed';
+
+DROP TABLE posts;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_old RENAME TO tracks
@@ -0,0 +1,53 @@
+-- Your SQL goes here
+CREATE TABLE posts (
+    id INTEGER PRIMARY KEY NOT NULL,
+    track_id INTEGER NOT NULL,
+    destination TEXT NOT NULL,
+    remote_id TEXT,
+    remote_url TEXT,
+    -- NULL for posts made before posts were recorded
+    posted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    status TEXT NOT NULL,
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+-- Nothing recorded when tracks were posted before this, so their posts have no
+-- posted_at rather than the time of the migration. They're treated as older
+-- than any post made since, so they come last in the history and in cooldowns
+-- and are reset by any `--older-than`
+
+-- Receipts of tracks that weren't marked as posted belong to runs where a
+-- required destination failed
+INSERT INTO posts (track_id, destination, remote_id, remote_url, posted_at, status)
+SELECT receipts.track_id, receipts.destination, receipts.remote_id, receipts.remote_url,
+    NULL, CASE tracks.posted WHEN 0 THEN 'incomplete' ELSE 'posted' END
+FROM receipts INNER JOIN tracks ON tracks.id = receipts.track_id;
+
+-- Tracks posted before receipts were stored were only ever posted to Twitter
+INSERT INTO posts (track_id, destination, posted_at, status)
+SELECT id, 'twitter', NULL, 'posted' FROM tracks
+WHERE posted != 0 AND id NOT IN (SELECT track_id FROM receipts);
+
+DROP TABLE receipts;
+
+CREATE TABLE tracks_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    playlist_id INTEGER,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE SET NULL
+);
+
+INSERT INTO tracks_new (id, spotify_id, playlist_id, name, url)
+SELECT id, spotify_id, playlist_id, name, url FROM tracks;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_new RENAME TO tracks
@@ -0,0 +1,25 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE tracks_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    playlist_id INTEGER,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE SET NULL
+);
+
+INSERT INTO tracks_old (id, spotify_id, playlist_id, name, url)
+SELECT id, spotify_id, playlist_id, name, url FROM tracks;
+
+DROP TABLE track_artists;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_old RENAME TO tracks;
+
+DROP TABLE artists;
+
+DROP TABLE albums
@@ -0,0 +1,37 @@
+-- Your SQL goes here
+CREATE TABLE albums (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    -- Spotify gives the year, year and month or full date depending on what's known
+    release_date TEXT,
+    image_url TEXT,
+    thumbnail_url TEXT
+);
+
+CREATE TABLE artists (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE track_artists (
+    track_id INTEGER NOT NULL,
+    artist_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (track_id, artist_id),
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE,
+    FOREIGN KEY (artist_id)
+        REFERENCES artists (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+ALTER TABLE tracks ADD COLUMN album_id INTEGER REFERENCES albums (id) ON DELETE SET NULL;
+ALTER TABLE tracks ADD COLUMN duration_ms INTEGER;
+ALTER TABLE tracks ADD COLUMN explicit BOOLEAN;
+ALTER TABLE tracks ADD COLUMN popularity INTEGER;
+ALTER TABLE tracks ADD COLUMN isrc TEXT
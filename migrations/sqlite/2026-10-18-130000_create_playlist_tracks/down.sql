@@ -0,0 +1,32 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE tracks_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    playlist_id INTEGER,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    album_id INTEGER REFERENCES albums (id) ON DELETE SET NULL,
+    duration_ms INTEGER,
+    explicit BOOLEAN,
+    popularity INTEGER,
+    isrc TEXT,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE SET NULL
+);
+
+-- A track can only belong to one playlist, so keep the first it was added to
+INSERT INTO tracks_old (id, spotify_id, playlist_id, name, url, album_id, duration_ms, explicit, popularity, isrc)
+SELECT id, spotify_id, (
+    SELECT playlist_id FROM playlist_tracks
+    WHERE playlist_tracks.track_id = tracks.id
+    ORDER BY added_at, playlist_id
+    LIMIT 1
+), name, url, album_id, duration_ms, explicit, popularity, isrc FROM tracks;
+
+DROP TABLE playlist_tracks;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_old RENAME TO tracks
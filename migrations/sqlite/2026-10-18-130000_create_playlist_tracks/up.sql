@@ -0,0 +1,40 @@
+-- Your SQL goes here
+CREATE TABLE playlist_tracks (
+    playlist_id INTEGER NOT NULL,
+    track_id INTEGER NOT NULL,
+    added_at TIMESTAMP,
+    -- The Spotify id of the user who added the track
+    added_by TEXT,
+    PRIMARY KEY (playlist_id, track_id),
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE,
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+-- When the tracks were added isn't known for existing memberships
+INSERT INTO playlist_tracks (playlist_id, track_id)
+SELECT playlist_id, id FROM tracks WHERE playlist_id IS NOT NULL;
+
+CREATE TABLE tracks_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    spotify_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    album_id INTEGER REFERENCES albums (id) ON DELETE SET NULL,
+    duration_ms INTEGER,
+    explicit BOOLEAN,
+    popularity INTEGER,
+    isrc TEXT
+);
+
+INSERT INTO tracks_new (id, spotify_id, name, url, album_id, duration_ms, explicit, popularity, isrc)
+SELECT id, spotify_id, name, url, album_id, duration_ms, explicit, popularity, isrc FROM tracks;
+
+DROP TABLE tracks;
+
+ALTER TABLE tracks_new RENAME TO tracks
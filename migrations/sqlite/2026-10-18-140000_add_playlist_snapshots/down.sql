@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE playlist_tracks DROP COLUMN removed_at;
+
+ALTER TABLE playlists DROP COLUMN snapshot_id
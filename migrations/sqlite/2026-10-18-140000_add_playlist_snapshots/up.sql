@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE playlists ADD COLUMN snapshot_id TEXT;
+
+-- Set when the track is no longer in the Spotify playlist
+ALTER TABLE playlist_tracks ADD COLUMN removed_at TIMESTAMP
@@ -0,0 +1,24 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE playlist_offset (
+    id INTEGER PRIMARY KEY NOT NULL,
+    playlist_id INTEGER NOT NULL,
+    offset INTEGER NOT NULL,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+INSERT INTO playlist_offset (playlist_id, offset)
+SELECT id, (
+    SELECT COUNT(*) FROM playlist_tracks
+    WHERE playlist_tracks.playlist_id = playlists.id AND removed_at IS NULL
+) FROM playlists;
+
+ALTER TABLE playlists ADD COLUMN snapshot_id TEXT;
+
+UPDATE playlists SET snapshot_id = (
+    SELECT snapshot_id FROM playlist_sync WHERE playlist_sync.playlist_id = playlists.id
+);
+
+DROP TABLE playlist_sync
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE TABLE playlist_sync (
+    playlist_id INTEGER PRIMARY KEY NOT NULL,
+    snapshot_id TEXT NOT NULL,
+    -- When the most recently added track we've seen was added to the playlist
+    last_added_at TIMESTAMP,
+    synced_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (playlist_id)
+        REFERENCES playlists (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+INSERT INTO playlist_sync (playlist_id, snapshot_id, last_added_at)
+SELECT id, snapshot_id, (
+    SELECT MAX(added_at) FROM playlist_tracks WHERE playlist_tracks.playlist_id = playlists.id
+) FROM playlists WHERE snapshot_id IS NOT NULL;
+
+ALTER TABLE playlists DROP COLUMN snapshot_id;
+
+DROP TABLE playlist_offset
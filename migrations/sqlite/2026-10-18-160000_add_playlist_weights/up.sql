@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- How likely the playlist is to be picked when choosing a track to post,
+-- relative to the other playlists
+ALTER TABLE playlists ADD COLUMN weight INTEGER NOT NULL DEFAULT 1
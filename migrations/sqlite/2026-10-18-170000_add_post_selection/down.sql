@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+-- SQLite can't drop a column that references another table so the table is rebuilt
+CREATE TABLE posts_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    track_id INTEGER NOT NULL,
+    destination TEXT NOT NULL,
+    remote_id TEXT,
+    remote_url TEXT,
+    posted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    status TEXT NOT NULL,
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+);
+
+INSERT INTO posts_old (id, track_id, destination, remote_id, remote_url, posted_at, status)
+SELECT id, track_id, destination, remote_id, remote_url, posted_at, status FROM posts;
+
+DROP TABLE posts;
+
+ALTER TABLE posts_old RENAME TO posts
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- When a posted track was put back in the pool to be posted again. The post
+-- is kept as history but no longer keeps its track out of the pool
+ALTER TABLE posts ADD COLUMN recycled_at TIMESTAMP
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- When each task run by `noi run` last ran, so runs missed while it was
+-- stopped can be caught up or skipped
+CREATE TABLE schedule_runs (
+    task TEXT PRIMARY KEY NOT NULL,
+    last_run_at TIMESTAMP NOT NULL
+)
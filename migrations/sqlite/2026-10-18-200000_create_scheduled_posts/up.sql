@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Tracks queued with `noi queue add` to be posted at a set time
+CREATE TABLE scheduled_posts (
+    id INTEGER PRIMARY KEY NOT NULL,
+    track_id INTEGER NOT NULL,
+    -- When the track is due to be posted, in UTC
+    scheduled_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (track_id)
+        REFERENCES tracks (id)
+            ON UPDATE NO ACTION
+            ON DELETE CASCADE
+)
@@ -0,0 +1,110 @@
+#[cfg(feature = "sqlite")]
+use diesel::{prelude::*, result::QueryResult, sqlite::SqliteConnection};
+use serde::Deserialize;
+
+/// The kinds of database that tracks, playlists and posts can be kept in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    /// Picks the backend from the scheme of a database URL. Anything that
+    /// isn't a Postgres URL is the path of a SQLite database
+    pub fn from_url(db_url: &str) -> Self {
+        if db_url.starts_with("postgres://") || db_url.starts_with("postgresql://") {
+            Backend::Postgres
+        } else {
+            Backend::Sqlite
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Sqlite => "sqlite",
+            Backend::Postgres => "postgres",
+        }
+    }
+}
+
+/// How SQLite keeps its journal, see https://www.sqlite.org/pragma.html#pragma_journal_mode
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    /// Lets tracks be read for a post while an update is writing to the database
+    #[default]
+    Wal,
+    Off,
+}
+
+impl JournalMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        }
+    }
+}
+
+/// How every connection to the database is set up. Postgres always enforces
+/// foreign keys and has no journal mode, so only the timeout applies to it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConnectionOptions {
+    /// Enforces foreign key constraints, which the `ON DELETE` clauses of the
+    /// tables rely on
+    pub foreign_keys: bool,
+    pub journal_mode: JournalMode,
+    /// How long to wait for another connection to finish writing before
+    /// failing with `database is locked`, in milliseconds. This is the
+    /// `lock_timeout` on Postgres
+    pub busy_timeout_ms: u32,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            foreign_keys: true,
+            journal_mode: JournalMode::default(),
+            busy_timeout_ms: 5000,
+        }
+    }
+}
+
+/// Applies the options that need to be set before migrations are run
+#[cfg(feature = "sqlite")]
+pub(crate) fn configure(conn: &SqliteConnection, options: &ConnectionOptions) -> QueryResult<()> {
+    // The timeout comes first so that changing the journal mode waits too
+    conn.execute(&format!(
+        "PRAGMA busy_timeout = {}",
+        options.busy_timeout_ms
+    ))?;
+    conn.execute(&format!(
+        "PRAGMA journal_mode = {}",
+        options.journal_mode.as_str()
+    ))?;
+    Ok(())
+}
+
+/// Applies the options that need to be set once migrations have run
+#[cfg(feature = "sqlite")]
+pub(crate) fn enforce_foreign_keys(
+    conn: &SqliteConnection,
+    options: &ConnectionOptions,
+) -> QueryResult<()> {
+    // Some migrations rebuild tables. Foreign keys are only enforced once they
+    // have run, otherwise dropping the old table would cascade into the tables
+    // that reference it
+    let foreign_keys = if options.foreign_keys { "ON" } else { "OFF" };
+    conn.execute(&format!("PRAGMA foreign_keys = {}", foreign_keys))?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::ConnectionError;
+use diesel_migrations::RunMigrationsError;
+use thiserror;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("No matching record was found")]
+    NotFound,
+    #[error("A matching record already exists: {0}")]
+    Duplicate(String),
+    #[error("A database constraint failed: {0}")]
+    Constraint(String),
+    #[error("Could not open the database: {0}")]
+    Io(String),
+    #[error("Could not bring the database schema up to date: {0}")]
+    Migration(#[from] RunMigrationsError),
+    #[error("DATABASE_URL is not set. Set it in .env to the path of the database")]
+    MissingDatabaseUrl,
+    #[error("This build doesn't support {0} databases. Rebuild it with `--features {0}`")]
+    UnsupportedBackend(&'static str),
+    #[error(transparent)]
+    Query(DieselError),
+}
+
+impl From<DieselError> for Error {
+    fn from(error: DieselError) -> Self {
+        match error {
+            DieselError::NotFound => Error::NotFound,
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                Error::Duplicate(info.message().to_string())
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) => {
+                Error::Constraint(info.message().to_string())
+            }
+            error => Error::Query(error),
+        }
+    }
+}
+
+impl From<ConnectionError> for Error {
+    fn from(error: ConnectionError) -> Self {
+        match error {
+            ConnectionError::CouldntSetupConfiguration(error) => error.into(),
+            error => Error::Io(error.to_string()),
+        }
+    }
+}
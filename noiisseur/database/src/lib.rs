@@ -1,116 +1,123 @@
-mod models;
+pub mod connection;
+pub mod error;
+#[cfg(any(test, feature = "test-util"))]
+mod memory;
+// diesel 1.4's derives and table! expand to impls that newer compilers lint.
+#[allow(unknown_lints, non_local_definitions)]
+pub mod models;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+mod queries;
+#[allow(unknown_lints, non_local_definitions)]
 mod schema;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod store;
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("Enable the `sqlite` or `postgres` feature to choose where data is stored");
 
 #[macro_use]
 extern crate diesel;
-use diesel::{prelude::*, result::QueryResult, sqlite::SqliteConnection};
+#[macro_use]
+extern crate diesel_migrations;
 
-use anyhow::Result;
+use chrono::NaiveDateTime;
 use dotenv::dotenv;
 use std::env;
 
-use models::{NewPlaylist, NewPlaylistOffset, NewTrack, Playlist, Track};
-use schema::{playlist_offset, playlists, tracks};
-
-pub fn establish_connection() -> Result<SqliteConnection> {
-    dotenv()?;
-    let db_url = env::var("DATABASE_URL")?;
-    let conn = SqliteConnection::establish(&db_url)?;
-    Ok(conn)
-}
-
-pub fn insert_track<'a>(
-    conn: &SqliteConnection,
-    spotify_id: &'a str,
-    playlist_id: i32,
-    name: &'a str,
-    url: &'a str,
-) -> QueryResult<usize> {
-    let track = NewTrack {
-        spotify_id,
-        playlist_id,
-        name,
-        url,
-    };
-
-    diesel::insert_into(tracks::table)
-        .values(&track)
-        .execute(conn)
-}
-
-pub fn delete_track(conn: &SqliteConnection, id: i32) {
-    diesel::delete(tracks::table.find(id))
-        .execute(conn)
-        .expect("Error deleting tracks");
+pub use connection::{Backend, ConnectionOptions, JournalMode};
+pub use error::{Error, Result};
+#[cfg(any(test, feature = "test-util"))]
+pub use memory::MemoryStore;
+use models::NewTrackDetails;
+#[cfg(feature = "postgres")]
+pub use postgres::PgStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+pub use store::Store;
+
+/// Connects to the database at `DATABASE_URL`, creating it if it doesn't exist
+pub fn establish(options: &ConnectionOptions) -> Result<Box<dyn Store>> {
+    dotenv().ok();
+    let db_url = env::var("DATABASE_URL").map_err(|_| Error::MissingDatabaseUrl)?;
+    connect(&db_url, options)
 }
 
-pub fn mark_track_as_posted(conn: &SqliteConnection, track: &Track) {
-    use crate::schema::tracks::columns::posted;
-    diesel::update(track)
-        .set(posted.eq(1))
-        .execute(conn)
-        .expect("Error updating track");
+/// Connects to a database using the backend its URL is for and runs any
+/// pending migrations
+pub fn connect(db_url: &str, options: &ConnectionOptions) -> Result<Box<dyn Store>> {
+    match Backend::from_url(db_url) {
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(SqliteStore::connect(db_url, options)?)),
+        #[cfg(feature = "postgres")]
+        Backend::Postgres => Ok(Box::new(PgStore::connect(db_url, options)?)),
+        #[allow(unreachable_patterns)]
+        backend => Err(Error::UnsupportedBackend(backend.name())),
+    }
 }
 
-pub fn get_tracks(conn: &SqliteConnection) -> Vec<Track> {
-    use crate::schema::tracks::columns::posted;
-    tracks::table
-        .filter(posted.eq(0))
-        .load::<Track>(conn)
-        .unwrap()
+/// The tracks affected by removing a playlist. Unposted tracks are deleted
+/// while posted tracks are kept as history. Tracks that are in another
+/// playlist too are kept in that playlist
+pub struct PlaylistRemoval {
+    pub unposted: i64,
+    pub posted: i64,
+    pub shared: i64,
 }
 
-pub fn insert_playlist<'a>(conn: &SqliteConnection, name: &'a str, spotify_id: &'a str) -> i32 {
-    use crate::schema::playlists::columns::id;
-
-    let playlist = NewPlaylist { name, spotify_id };
-
-    diesel::insert_into(playlists::table)
-        .values(&playlist)
-        .execute(conn)
-        .expect("Error inserting playlist into database");
-
-    let playlist_id = playlists::table
-        .select(id)
-        .order(id.desc())
-        .limit(1)
-        .load::<i32>(conn)
-        .unwrap()[0];
-
-    playlist_id
+/// What changed in a playlist since it was last synced
+pub struct SyncReport {
+    pub added: usize,
+    pub removed: usize,
 }
 
-pub fn get_playlists(conn: &SqliteConnection) -> Vec<Playlist> {
-    playlists::table.load::<Playlist>(conn).unwrap()
+/// A track listed in a playlist, with what's stored of it that a sync
+/// compares with the track fetched from Spotify
+pub(crate) struct Listing {
+    pub track_id: i32,
+    /// The Spotify id of the album
+    pub album: Option<String>,
+    /// The Spotify ids of the artists, in the order they're credited
+    pub artists: Vec<String>,
+    pub added_at: Option<NaiveDateTime>,
 }
 
-pub fn insert_playlist_offset(conn: &SqliteConnection, playlist_id: i32, offset: i32) {
-    let offset = NewPlaylistOffset {
-        offset,
-        playlist_id,
-    };
-
-    diesel::insert_into(playlist_offset::table)
-        .values(&offset)
-        .execute(conn)
-        .expect("Error inserting playlist offset into database");
-}
-
-pub fn update_playlist_offset(conn: &SqliteConnection, playlist_id: i32, offset_val: i32) {
-    use crate::schema::playlist_offset::columns::{id, offset};
-    diesel::update(playlist_offset::table.filter(id.eq(playlist_id)))
-        .set(offset.eq(offset_val))
-        .execute(conn)
-        .unwrap();
+impl Listing {
+    /// The listing a fetched track is stored as
+    pub fn new(track_id: i32, track: &NewTrackDetails) -> Self {
+        Listing {
+            track_id,
+            album: track
+                .album
+                .as_ref()
+                .map(|album| album.spotify_id.to_string()),
+            artists: track
+                .artists
+                .iter()
+                .map(|artist| artist.spotify_id.to_string())
+                .collect(),
+            added_at: track.added_at,
+        }
+    }
+
+    /// Whether the fetched track's album, artists or `added_at` differ from
+    /// what's stored, e.g. because the listing was stored before they were kept
+    pub fn is_stale(&self, track: &NewTrackDetails) -> bool {
+        let artists = track.artists.iter().map(|artist| artist.spotify_id);
+        self.album.as_deref() != track.album.as_ref().map(|album| album.spotify_id)
+            || !self.artists.iter().map(String::as_str).eq(artists)
+            || self.added_at != track.added_at
+    }
 }
 
-pub fn get_playlist_offset(conn: &SqliteConnection, playlist_id_val: i32) -> i32 {
-    use crate::schema::playlist_offset::columns::{offset, playlist_id};
-    let offset_val = playlist_offset::table
-        .filter(playlist_id.eq(playlist_id_val))
-        .select(offset)
-        .first::<i32>(conn)
-        .unwrap();
-
-    offset_val
+/// Whether a track was added to the playlist after the last seen `added_at`.
+/// These may be re-adds of tracks we already have, which need their
+/// membership refreshed
+pub(crate) fn added_since(track: &NewTrackDetails, watermark: Option<NaiveDateTime>) -> bool {
+    match (track.added_at, watermark) {
+        (Some(added_at), Some(watermark)) => added_at > watermark,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
 }
@@ -0,0 +1,779 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{
+    added_since,
+    error::{Error, Result},
+    models::{
+        Album, Artist, NewAlbum, NewArtist, NewTrackDetails, Playlist, PlaylistEntry, PlaylistSync,
+        PlaylistTrack, Post, PostStatus, ScheduleRun, ScheduledPost, Selection, Track,
+        TrackDetails,
+    },
+    store::Store,
+    Listing, PlaylistRemoval, SyncReport,
+};
+
+#[derive(Default)]
+struct Tables {
+    albums: Vec<Album>,
+    artists: Vec<Artist>,
+    playlist_sync: Vec<PlaylistSync>,
+    playlist_tracks: Vec<PlaylistTrack>,
+    playlists: Vec<Playlist>,
+    posts: Vec<Post>,
+    schedule_runs: Vec<ScheduleRun>,
+    scheduled_posts: Vec<ScheduledPost>,
+    /// The track id, artist id and position of each credited artist
+    track_artists: Vec<(i32, i32, i32)>,
+    tracks: Vec<Track>,
+}
+
+/// A store that keeps everything in memory, for tests. It behaves like the
+/// SQLite store with foreign keys enforced
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: RefCell<Tables>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+/// The id SQLite would give the next row of a table
+fn next_id<T>(rows: &[T], id: impl Fn(&T) -> i32) -> i32 {
+    rows.iter().map(id).max().unwrap_or(0) + 1
+}
+
+fn foreign_key_violation() -> Error {
+    Error::Constraint("FOREIGN KEY constraint failed".to_string())
+}
+
+impl Tables {
+    fn save_album(&mut self, album: &NewAlbum) -> i32 {
+        let new = Album {
+            id: next_id(&self.albums, |a| a.id),
+            spotify_id: album.spotify_id.to_string(),
+            name: album.name.to_string(),
+            release_date: album.release_date.map(str::to_string),
+            image_url: album.image_url.map(str::to_string),
+            thumbnail_url: album.thumbnail_url.map(str::to_string),
+        };
+        match self
+            .albums
+            .iter_mut()
+            .find(|a| a.spotify_id == album.spotify_id)
+        {
+            Some(existing) => {
+                *existing = Album {
+                    id: existing.id,
+                    ..new
+                };
+                existing.id
+            }
+            None => {
+                let id = new.id;
+                self.albums.push(new);
+                id
+            }
+        }
+    }
+
+    fn save_artist(&mut self, artist: &NewArtist) -> i32 {
+        let id = next_id(&self.artists, |a| a.id);
+        match self
+            .artists
+            .iter_mut()
+            .find(|a| a.spotify_id == artist.spotify_id)
+        {
+            Some(existing) => {
+                existing.name = artist.name.to_string();
+                existing.id
+            }
+            None => {
+                self.artists.push(Artist {
+                    id,
+                    spotify_id: artist.spotify_id.to_string(),
+                    name: artist.name.to_string(),
+                });
+                id
+            }
+        }
+    }
+
+    fn save_track(&mut self, playlist_id: i32, details: &NewTrackDetails) -> Result<i32> {
+        if !self.playlists.iter().any(|p| p.id == playlist_id) {
+            return Err(foreign_key_violation());
+        }
+
+        let album_id = details.album.as_ref().map(|album| self.save_album(album));
+        let new = Track {
+            id: next_id(&self.tracks, |t| t.id),
+            spotify_id: details.spotify_id.to_string(),
+            name: details.name.to_string(),
+            url: details.url.to_string(),
+            album_id,
+            duration_ms: details.duration_ms,
+            explicit: details.explicit,
+            popularity: details.popularity,
+            isrc: details.isrc.map(str::to_string),
+        };
+        let track_id = match self
+            .tracks
+            .iter_mut()
+            .find(|t| t.spotify_id == details.spotify_id)
+        {
+            Some(existing) => {
+                *existing = Track {
+                    id: existing.id,
+                    ..new
+                };
+                existing.id
+            }
+            None => {
+                let id = new.id;
+                self.tracks.push(new);
+                id
+            }
+        };
+
+        self.track_artists.retain(|(id, _, _)| *id != track_id);
+        for (position, artist) in details.artists.iter().enumerate() {
+            let artist_id = self.save_artist(artist);
+            self.track_artists
+                .push((track_id, artist_id, position as i32));
+        }
+
+        self.playlist_tracks
+            .retain(|pt| !(pt.playlist_id == playlist_id && pt.track_id == track_id));
+        self.playlist_tracks.push(PlaylistTrack {
+            playlist_id,
+            track_id,
+            added_at: details.added_at,
+            added_by: details.added_by.map(str::to_string),
+            removed_at: None,
+        });
+
+        Ok(track_id)
+    }
+
+    fn delete_track(&mut self, track_id: i32) {
+        self.tracks.retain(|t| t.id != track_id);
+        self.track_artists.retain(|(id, _, _)| *id != track_id);
+        self.playlist_tracks.retain(|pt| pt.track_id != track_id);
+        self.posts.retain(|p| p.track_id != track_id);
+        self.scheduled_posts.retain(|s| s.track_id != track_id);
+    }
+
+    /// The tracks listed in a playlist, by their Spotify id
+    fn playlist_listings(&self, playlist_id: i32) -> HashMap<String, Listing> {
+        self.playlist_tracks
+            .iter()
+            .filter(|pt| pt.playlist_id == playlist_id && pt.removed_at.is_none())
+            .filter_map(|pt| {
+                let track = self.tracks.iter().find(|t| t.id == pt.track_id)?;
+                let album = track
+                    .album_id
+                    .and_then(|album_id| self.albums.iter().find(|a| a.id == album_id));
+                let artists = self
+                    .artist_ids(track.id)
+                    .into_iter()
+                    .filter_map(|artist_id| self.artists.iter().find(|a| a.id == artist_id))
+                    .map(|artist| artist.spotify_id.clone())
+                    .collect();
+                let listing = Listing {
+                    track_id: track.id,
+                    album: album.map(|album| album.spotify_id.clone()),
+                    artists,
+                    added_at: pt.added_at,
+                };
+                Some((track.spotify_id.clone(), listing))
+            })
+            .collect()
+    }
+
+    /// The ids of a track's artists, in the order they're credited
+    fn artist_ids(&self, track_id: i32) -> Vec<i32> {
+        let mut credits: Vec<&(i32, i32, i32)> = self
+            .track_artists
+            .iter()
+            .filter(|(id, _, _)| *id == track_id)
+            .collect();
+        credits.sort_by_key(|(_, _, position)| *position);
+        credits
+            .into_iter()
+            .map(|(_, artist_id, _)| *artist_id)
+            .collect()
+    }
+
+    /// The posts that are still live, which are the ones that were posted and
+    /// haven't been undone since
+    fn live_posts(&self) -> impl Iterator<Item = &Post> {
+        let undone: HashSet<i32> = self.posts.iter().filter_map(|p| p.undone_post_id).collect();
+        self.posts
+            .iter()
+            .filter(move |p| p.status == PostStatus::Posted.as_str() && !undone.contains(&p.id))
+    }
+
+    /// Whether a track has been posted and not put back in the pool since
+    fn is_posted(&self, track_id: i32) -> bool {
+        self.live_posts()
+            .any(|p| p.track_id == track_id && p.recycled_at.is_none())
+    }
+
+    /// Whether a track has ever been posted, even if it was put back in the pool
+    fn was_posted(&self, track_id: i32) -> bool {
+        self.live_posts().any(|p| p.track_id == track_id)
+    }
+
+    /// The playlists a track is in, in the order it was added to them
+    fn track_playlists(&self, track_id: i32, include_removed: bool) -> Vec<Playlist> {
+        let mut memberships: Vec<&PlaylistTrack> = self
+            .playlist_tracks
+            .iter()
+            .filter(|pt| pt.track_id == track_id)
+            .filter(|pt| include_removed || pt.removed_at.is_none())
+            .collect();
+        memberships.sort_by_key(|pt| (pt.added_at, pt.playlist_id));
+        memberships
+            .into_iter()
+            .filter_map(|pt| self.playlists.iter().find(|p| p.id == pt.playlist_id))
+            .cloned()
+            .collect()
+    }
+
+    fn playlist_track_ids(&self, playlist_id: i32) -> HashSet<i32> {
+        self.playlist_tracks
+            .iter()
+            .filter(|pt| pt.playlist_id == playlist_id)
+            .map(|pt| pt.track_id)
+            .collect()
+    }
+
+    fn other_playlist_track_ids(&self, playlist_id: i32) -> HashSet<i32> {
+        self.playlist_tracks
+            .iter()
+            .filter(|pt| pt.playlist_id != playlist_id)
+            .map(|pt| pt.track_id)
+            .collect()
+    }
+
+    /// The tracks only in this playlist, split into unposted and posted
+    fn removable_tracks(&self, playlist_id: i32) -> (Vec<i32>, Vec<i32>) {
+        let members = self.playlist_track_ids(playlist_id);
+        let others = self.other_playlist_track_ids(playlist_id);
+        self.tracks
+            .iter()
+            .map(|t| t.id)
+            .filter(|id| members.contains(id))
+            .filter(|id| !others.contains(id))
+            .partition(|id| !self.was_posted(*id))
+    }
+
+    fn get_playlist_removal(&self, playlist_id: i32) -> PlaylistRemoval {
+        let (unposted, posted) = self.removable_tracks(playlist_id);
+        let others = self.other_playlist_track_ids(playlist_id);
+        let shared = self
+            .playlist_track_ids(playlist_id)
+            .iter()
+            .filter(|id| others.contains(id))
+            .count();
+
+        PlaylistRemoval {
+            unposted: unposted.len() as i64,
+            posted: posted.len() as i64,
+            shared: shared as i64,
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn save_track(&self, playlist_id: i32, details: &NewTrackDetails) -> Result<i32> {
+        self.tables.borrow_mut().save_track(playlist_id, details)
+    }
+
+    fn delete_track(&self, track_id: i32) -> Result<()> {
+        self.tables.borrow_mut().delete_track(track_id);
+        Ok(())
+    }
+
+    fn get_track(&self, track_id: i32) -> Result<Track> {
+        self.tables
+            .borrow()
+            .tracks
+            .iter()
+            .find(|t| t.id == track_id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    fn get_track_by_spotify_id(&self, spotify_id: &str) -> Result<Option<Track>> {
+        let tables = self.tables.borrow();
+        let track = tables.tracks.iter().find(|t| t.spotify_id == spotify_id);
+        Ok(track.cloned())
+    }
+
+    fn get_track_details(&self, track: &Track) -> Result<TrackDetails> {
+        let tables = self.tables.borrow();
+        let album = track
+            .album_id
+            .and_then(|album_id| tables.albums.iter().find(|a| a.id == album_id))
+            .cloned();
+        let mut credits: Vec<&(i32, i32, i32)> = tables
+            .track_artists
+            .iter()
+            .filter(|(track_id, _, _)| *track_id == track.id)
+            .collect();
+        credits.sort_by_key(|(_, _, position)| *position);
+        let artists = credits
+            .into_iter()
+            .filter_map(|(_, artist_id, _)| tables.artists.iter().find(|a| a.id == *artist_id))
+            .cloned()
+            .collect();
+
+        Ok(TrackDetails {
+            track: track.clone(),
+            playlists: tables.track_playlists(track.id, false),
+            album,
+            artists,
+        })
+    }
+
+    fn get_tracks(&self) -> Result<Vec<Track>> {
+        let tables = self.tables.borrow();
+        let tracks = tables
+            .tracks
+            .iter()
+            .filter(|t| !tables.is_posted(t.id))
+            .filter(|t| {
+                tables
+                    .playlist_tracks
+                    .iter()
+                    .any(|pt| pt.track_id == t.id && pt.removed_at.is_none())
+            })
+            .cloned()
+            .collect();
+        Ok(tracks)
+    }
+
+    fn is_track_posted(&self, track_id: i32) -> Result<bool> {
+        Ok(self.tables.borrow().is_posted(track_id))
+    }
+
+    fn insert_post(
+        &self,
+        track_id: i32,
+        destination: &str,
+        remote_id: Option<&str>,
+        remote_url: Option<&str>,
+        status: PostStatus,
+        selection: &Selection,
+    ) -> Result<usize> {
+        let mut tables = self.tables.borrow_mut();
+        if !tables.tracks.iter().any(|t| t.id == track_id) {
+            return Err(foreign_key_violation());
+        }
+        if let Some(playlist_id) = selection.playlist_id {
+            if !tables.playlists.iter().any(|p| p.id == playlist_id) {
+                return Err(foreign_key_violation());
+            }
+        }
+
+        let post = Post {
+            id: next_id(&tables.posts, |p| p.id),
+            track_id,
+            destination: destination.to_string(),
+            remote_id: remote_id.map(str::to_string),
+            remote_url: remote_url.map(str::to_string),
+            posted_at: Some(Utc::now().naive_utc()),
+            status: status.as_str().to_string(),
+            playlist_id: selection.playlist_id,
+            strategy: Some(selection.strategy.to_string()),
+            recycled_at: None,
+            undone_post_id: None,
+        };
+        tables.posts.push(post);
+        Ok(1)
+    }
+
+    fn get_post(&self, post_id: i32) -> Result<Option<Post>> {
+        let tables = self.tables.borrow();
+        Ok(tables.posts.iter().find(|p| p.id == post_id).cloned())
+    }
+
+    fn get_last_post(&self) -> Result<Option<Post>> {
+        let tables = self.tables.borrow();
+        let post = tables
+            .live_posts()
+            .max_by_key(|p| (p.posted_at, p.id))
+            .cloned();
+        Ok(post)
+    }
+
+    fn get_published_posts(&self, post: &Post) -> Result<Vec<Post>> {
+        let tables = self.tables.borrow();
+        let posts = tables
+            .live_posts()
+            .filter(|p| p.track_id == post.track_id && p.recycled_at == post.recycled_at)
+            .cloned()
+            .collect();
+        Ok(posts)
+    }
+
+    fn get_recently_posted_tracks(&self, limit: usize) -> Result<Vec<Track>> {
+        let tables = self.tables.borrow();
+        let mut posts: Vec<&Post> = tables.live_posts().collect();
+        posts.sort_by_key(|p| std::cmp::Reverse((p.posted_at, p.id)));
+
+        let mut seen = HashSet::new();
+        let tracks = posts
+            .into_iter()
+            .filter(|p| seen.insert(p.track_id))
+            .take(limit)
+            .filter_map(|p| tables.tracks.iter().find(|t| t.id == p.track_id))
+            .cloned()
+            .collect();
+        Ok(tracks)
+    }
+
+    fn reset_posted_tracks(
+        &self,
+        playlist_id: Option<i32>,
+        posted_before: Option<NaiveDateTime>,
+    ) -> Result<usize> {
+        let mut tables = self.tables.borrow_mut();
+        let members = playlist_id.map(|playlist_id| tables.playlist_track_ids(playlist_id));
+        let mut last_posted: HashMap<i32, Option<NaiveDateTime>> = HashMap::new();
+        for post in tables.live_posts() {
+            if post.recycled_at.is_some() {
+                continue;
+            }
+            if members
+                .as_ref()
+                .is_some_and(|m| !m.contains(&post.track_id))
+            {
+                continue;
+            }
+            let last = last_posted.entry(post.track_id).or_insert(post.posted_at);
+            *last = (*last).max(post.posted_at);
+        }
+        let track_ids: HashSet<i32> = last_posted
+            .into_iter()
+            .filter(|(_, last)| posted_before.is_none_or(|before| *last < Some(before)))
+            .map(|(track_id, _)| track_id)
+            .collect();
+
+        let recycled: HashSet<i32> = tables
+            .live_posts()
+            .filter(|p| track_ids.contains(&p.track_id) && p.recycled_at.is_none())
+            .map(|p| p.id)
+            .collect();
+        let now = Utc::now().naive_utc();
+        for post in tables.posts.iter_mut() {
+            if recycled.contains(&post.id) {
+                post.recycled_at = Some(now);
+            }
+        }
+        Ok(track_ids.len())
+    }
+
+    fn undo_posts(&self, posts: &[Post]) -> Result<usize> {
+        let mut tables = self.tables.borrow_mut();
+        if posts
+            .iter()
+            .any(|post| !tables.posts.iter().any(|p| p.id == post.id))
+        {
+            return Err(foreign_key_violation());
+        }
+
+        let now = Utc::now().naive_utc();
+        for post in posts {
+            let undo = Post {
+                id: next_id(&tables.posts, |p| p.id),
+                posted_at: Some(now),
+                status: PostStatus::Undone.as_str().to_string(),
+                recycled_at: None,
+                undone_post_id: Some(post.id),
+                ..post.clone()
+            };
+            tables.posts.push(undo);
+        }
+        Ok(posts.len())
+    }
+
+    fn get_post_history(
+        &self,
+        since: Option<NaiveDateTime>,
+        playlist_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<(Post, Track, Vec<Playlist>)>> {
+        let tables = self.tables.borrow();
+        let in_playlist = playlist_id.map(|playlist_id| tables.playlist_track_ids(playlist_id));
+
+        let mut posts: Vec<&Post> = tables
+            .posts
+            .iter()
+            .filter(|p| since.is_none_or(|since| p.posted_at >= Some(since)))
+            .filter(|p| {
+                in_playlist
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&p.track_id))
+            })
+            .collect();
+        posts.sort_by_key(|p| std::cmp::Reverse((p.posted_at, p.id)));
+
+        let history = posts
+            .into_iter()
+            .filter_map(|post| {
+                let track = tables.tracks.iter().find(|t| t.id == post.track_id)?;
+                Some((
+                    post.clone(),
+                    track.clone(),
+                    tables.track_playlists(track.id, true),
+                ))
+            })
+            .take(limit.max(0) as usize)
+            .collect();
+        Ok(history)
+    }
+
+    fn insert_playlist(&self, name: &str, spotify_id: &str) -> Result<i32> {
+        let mut tables = self.tables.borrow_mut();
+        if tables.playlists.iter().any(|p| p.spotify_id == spotify_id) {
+            return Err(Error::Duplicate(
+                "UNIQUE constraint failed: playlists.spotify_id".to_string(),
+            ));
+        }
+
+        let id = next_id(&tables.playlists, |p| p.id);
+        tables.playlists.push(Playlist {
+            id,
+            spotify_id: spotify_id.to_string(),
+            name: name.to_string(),
+            weight: 1,
+        });
+        Ok(id)
+    }
+
+    fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        Ok(self.tables.borrow().playlists.clone())
+    }
+
+    fn set_playlist_weight(&self, playlist_id: i32, weight: i32) -> Result<usize> {
+        let mut tables = self.tables.borrow_mut();
+        let updated = tables
+            .playlists
+            .iter_mut()
+            .filter(|p| p.id == playlist_id)
+            .map(|p| p.weight = weight)
+            .count();
+        Ok(updated)
+    }
+
+    fn get_tracks_by_playlist(&self) -> Result<Vec<(Playlist, Vec<PlaylistEntry>)>> {
+        let tables = self.tables.borrow();
+        let tracks_by_playlist = tables
+            .playlists
+            .iter()
+            .map(|playlist| {
+                let mut entries: Vec<PlaylistEntry> = tables
+                    .playlist_tracks
+                    .iter()
+                    .filter(|pt| pt.playlist_id == playlist.id && pt.removed_at.is_none())
+                    .filter(|pt| !tables.is_posted(pt.track_id))
+                    .filter_map(|pt| {
+                        let track = tables.tracks.iter().find(|t| t.id == pt.track_id)?;
+                        Some(PlaylistEntry {
+                            track: track.clone(),
+                            added_at: pt.added_at,
+                            artist_ids: tables.artist_ids(track.id),
+                        })
+                    })
+                    .collect();
+                entries.sort_by_key(|entry| entry.track.id);
+                (playlist.clone(), entries)
+            })
+            .collect();
+        Ok(tracks_by_playlist)
+    }
+
+    fn get_playlist(&self, identifier: &str) -> Result<Option<Playlist>> {
+        let tables = self.tables.borrow();
+        let playlist = tables
+            .playlists
+            .iter()
+            .find(|p| p.spotify_id == identifier)
+            .or_else(|| tables.playlists.iter().find(|p| p.name == identifier))
+            .cloned();
+        Ok(playlist)
+    }
+
+    fn get_playlist_removal(&self, playlist_id: i32) -> Result<PlaylistRemoval> {
+        Ok(self.tables.borrow().get_playlist_removal(playlist_id))
+    }
+
+    fn remove_playlist(&self, playlist_id: i32) -> Result<PlaylistRemoval> {
+        let mut tables = self.tables.borrow_mut();
+        let removal = tables.get_playlist_removal(playlist_id);
+
+        // Posted tracks are kept so we don't lose the history of what was posted
+        let (unposted, _) = tables.removable_tracks(playlist_id);
+        for track_id in unposted {
+            tables.delete_track(track_id);
+        }
+        tables
+            .playlist_tracks
+            .retain(|pt| pt.playlist_id != playlist_id);
+        tables
+            .playlist_sync
+            .retain(|s| s.playlist_id != playlist_id);
+        for post in tables.posts.iter_mut() {
+            if post.playlist_id == Some(playlist_id) {
+                post.playlist_id = None;
+            }
+        }
+        tables.playlists.retain(|p| p.id != playlist_id);
+
+        Ok(removal)
+    }
+
+    fn get_playlist_sync(&self, playlist_id: i32) -> Result<Option<PlaylistSync>> {
+        let tables = self.tables.borrow();
+        let sync = tables
+            .playlist_sync
+            .iter()
+            .find(|s| s.playlist_id == playlist_id)
+            .cloned();
+        Ok(sync)
+    }
+
+    fn sync_playlist(
+        &self,
+        playlist_id: i32,
+        snapshot_id: &str,
+        tracks: &[NewTrackDetails],
+    ) -> Result<SyncReport> {
+        let watermark = self
+            .get_playlist_sync(playlist_id)?
+            .and_then(|sync| sync.last_added_at);
+        let mut tables = self.tables.borrow_mut();
+        let mut listed = tables.playlist_listings(playlist_id);
+
+        let mut added = 0;
+        let mut present = HashSet::new();
+        for track in tracks.iter() {
+            let track_id = match listed.get(track.spotify_id) {
+                Some(listing) if !added_since(track, watermark) && !listing.is_stale(track) => {
+                    listing.track_id
+                }
+                Some(_) => tables.save_track(playlist_id, track)?,
+                None => {
+                    let track_id = tables.save_track(playlist_id, track)?;
+                    listed.insert(track.spotify_id.to_string(), Listing::new(track_id, track));
+                    added += 1;
+                    track_id
+                }
+            };
+            present.insert(track_id);
+        }
+
+        let now = Utc::now().naive_utc();
+        let mut removed = 0;
+        for membership in tables.playlist_tracks.iter_mut() {
+            if membership.playlist_id == playlist_id
+                && membership.removed_at.is_none()
+                && !present.contains(&membership.track_id)
+            {
+                membership.removed_at = Some(now);
+                removed += 1;
+            }
+        }
+
+        let sync = PlaylistSync {
+            playlist_id,
+            snapshot_id: snapshot_id.to_string(),
+            last_added_at: tracks
+                .iter()
+                .filter_map(|track| track.added_at)
+                .max()
+                .max(watermark),
+            synced_at: now,
+        };
+        tables
+            .playlist_sync
+            .retain(|s| s.playlist_id != playlist_id);
+        tables.playlist_sync.push(sync);
+
+        Ok(SyncReport { added, removed })
+    }
+
+    fn get_last_run(&self, task: &str) -> Result<Option<NaiveDateTime>> {
+        let tables = self.tables.borrow();
+        let last_run_at = tables
+            .schedule_runs
+            .iter()
+            .find(|r| r.task == task)
+            .map(|r| r.last_run_at);
+        Ok(last_run_at)
+    }
+
+    fn set_last_run(&self, task: &str, last_run_at: NaiveDateTime) -> Result<usize> {
+        let mut tables = self.tables.borrow_mut();
+        tables.schedule_runs.retain(|r| r.task != task);
+        tables.schedule_runs.push(ScheduleRun {
+            task: task.to_string(),
+            last_run_at,
+        });
+        Ok(1)
+    }
+
+    fn insert_scheduled_post(&self, track_id: i32, scheduled_at: NaiveDateTime) -> Result<i32> {
+        let mut tables = self.tables.borrow_mut();
+        if !tables.tracks.iter().any(|t| t.id == track_id) {
+            return Err(foreign_key_violation());
+        }
+
+        let id = next_id(&tables.scheduled_posts, |s| s.id);
+        tables.scheduled_posts.push(ScheduledPost {
+            id,
+            track_id,
+            scheduled_at,
+            created_at: Utc::now().naive_utc(),
+        });
+        Ok(id)
+    }
+
+    fn get_scheduled_posts(&self) -> Result<Vec<(ScheduledPost, Track)>> {
+        let tables = self.tables.borrow();
+        let mut scheduled: Vec<(ScheduledPost, Track)> = tables
+            .scheduled_posts
+            .iter()
+            .filter_map(|s| {
+                let track = tables.tracks.iter().find(|t| t.id == s.track_id)?;
+                Some((s.clone(), track.clone()))
+            })
+            .collect();
+        scheduled.sort_by_key(|(s, _)| (s.scheduled_at, s.id));
+        Ok(scheduled)
+    }
+
+    fn delete_scheduled_post(&self, scheduled_post_id: i32) -> Result<usize> {
+        let mut tables = self.tables.borrow_mut();
+        let before = tables.scheduled_posts.len();
+        tables.scheduled_posts.retain(|s| s.id != scheduled_post_id);
+        Ok(before - tables.scheduled_posts.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::store_tests;
+
+    store_tests!(MemoryStore::new());
+}
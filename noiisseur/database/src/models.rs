@@ -1,30 +1,136 @@
-use crate::schema::{playlist_offset, playlists, tracks};
+use crate::schema::{
+    albums, artists, playlist_sync, playlist_tracks, playlists, posts, schedule_runs,
+    scheduled_posts, track_artists, tracks,
+};
+use chrono::NaiveDateTime;
 use diesel::{Insertable, Queryable};
 
-#[derive(Queryable, Identifiable, PartialEq)]
+#[derive(Queryable, Identifiable, PartialEq, Clone)]
 pub struct Track {
     pub id: i32,
     pub spotify_id: String,
-    pub playlist_id: i32,
     pub name: String,
     pub url: String,
-    pub posted: i32,
+    pub album_id: Option<i32>,
+    pub duration_ms: Option<i32>,
+    pub explicit: Option<bool>,
+    pub popularity: Option<i32>,
+    pub isrc: Option<String>,
 }
 
 #[derive(Insertable)]
 #[table_name = "tracks"]
 pub struct NewTrack<'a> {
     pub spotify_id: &'a str,
-    pub playlist_id: i32,
     pub name: &'a str,
     pub url: &'a str,
+    pub album_id: Option<i32>,
+    pub duration_ms: Option<i32>,
+    pub explicit: Option<bool>,
+    pub popularity: Option<i32>,
+    pub isrc: Option<&'a str>,
+}
+
+/// Refreshes the details of a track that is already stored
+#[derive(AsChangeset)]
+#[table_name = "tracks"]
+pub struct TrackChanges<'a> {
+    pub name: &'a str,
+    pub url: &'a str,
+    pub album_id: Option<i32>,
+    pub duration_ms: Option<i32>,
+    pub explicit: Option<bool>,
+    pub popularity: Option<i32>,
+    pub isrc: Option<&'a str>,
+}
+
+#[derive(Queryable, PartialEq, Clone)]
+pub struct Album {
+    pub id: i32,
+    pub spotify_id: String,
+    pub name: String,
+    pub release_date: Option<String>,
+    pub image_url: Option<String>,
+    pub thumbnail_url: Option<String>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "albums"]
+pub struct NewAlbum<'a> {
+    pub spotify_id: &'a str,
+    pub name: &'a str,
+    pub release_date: Option<&'a str>,
+    pub image_url: Option<&'a str>,
+    pub thumbnail_url: Option<&'a str>,
+}
+
+#[derive(Queryable, PartialEq, Clone)]
+pub struct Artist {
+    pub id: i32,
+    pub spotify_id: String,
+    pub name: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "artists"]
+pub struct NewArtist<'a> {
+    pub spotify_id: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[table_name = "track_artists"]
+pub struct NewTrackArtist {
+    pub track_id: i32,
+    pub artist_id: i32,
+    pub position: i32,
+}
+
+/// A track fetched from Spotify along with its album and artists, in the
+/// order they're credited, and who added it to the playlist and when
+pub struct NewTrackDetails<'a> {
+    pub spotify_id: &'a str,
+    pub name: &'a str,
+    pub url: &'a str,
+    pub duration_ms: Option<i32>,
+    pub explicit: Option<bool>,
+    pub popularity: Option<i32>,
+    pub isrc: Option<&'a str>,
+    pub album: Option<NewAlbum<'a>>,
+    pub artists: Vec<NewArtist<'a>>,
+    pub added_at: Option<NaiveDateTime>,
+    pub added_by: Option<&'a str>,
+}
+
+/// A stored track with everything known about it
+#[derive(Clone)]
+pub struct TrackDetails {
+    pub track: Track,
+    /// The playlists the track is still in, in the order it was added to them
+    pub playlists: Vec<Playlist>,
+    pub album: Option<Album>,
+    pub artists: Vec<Artist>,
 }
 
-#[derive(Queryable, PartialEq)]
+impl TrackDetails {
+    /// The names of the track's artists e.g. `Khruangbin, Leon Bridges`
+    pub fn artist_names(&self) -> String {
+        self.artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Queryable, PartialEq, Clone)]
 pub struct Playlist {
     pub id: i32,
     pub spotify_id: String,
     pub name: String,
+    /// How likely the playlist is to be picked relative to the others. A
+    /// playlist with a weight of 0 is never picked
+    pub weight: i32,
 }
 
 #[derive(Insertable)]
@@ -34,16 +140,151 @@ pub struct NewPlaylist<'a> {
     pub spotify_id: &'a str,
 }
 
-#[derive(Queryable, PartialEq)]
-pub struct PlaylistOffset {
-    pub id: i32,
-    pub offset: i32,
+/// A track's membership of a playlist
+#[derive(Queryable, PartialEq, Clone)]
+pub struct PlaylistTrack {
+    pub playlist_id: i32,
+    pub track_id: i32,
+    pub added_at: Option<NaiveDateTime>,
+    pub added_by: Option<String>,
+    /// When the track was removed from the playlist on Spotify
+    pub removed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "playlist_tracks"]
+pub struct NewPlaylistTrack<'a> {
+    pub playlist_id: i32,
+    pub track_id: i32,
+    pub added_at: Option<NaiveDateTime>,
+    pub added_by: Option<&'a str>,
+}
+
+/// A track that hasn't been posted yet, as it's listed in one of its playlists
+#[derive(Clone)]
+pub struct PlaylistEntry {
+    pub track: Track,
+    /// When the track was added to the playlist, if it's known
+    pub added_at: Option<NaiveDateTime>,
+    /// The ids of the track's artists, in the order they're credited
+    pub artist_ids: Vec<i32>,
+}
+
+/// How far a playlist has been synced with Spotify
+#[derive(Queryable, PartialEq, Clone)]
+pub struct PlaylistSync {
     pub playlist_id: i32,
+    /// The Spotify snapshot id of the playlist when it was last synced
+    pub snapshot_id: String,
+    /// When the most recently added track we've seen was added to the playlist
+    pub last_added_at: Option<NaiveDateTime>,
+    pub synced_at: NaiveDateTime,
 }
 
 #[derive(Insertable)]
-#[table_name = "playlist_offset"]
-pub struct NewPlaylistOffset {
-    pub offset: i32,
+#[table_name = "playlist_sync"]
+pub struct NewPlaylistSync<'a> {
     pub playlist_id: i32,
+    pub snapshot_id: &'a str,
+    pub last_added_at: Option<NaiveDateTime>,
+    pub synced_at: NaiveDateTime,
+}
+
+/// When a task run by the scheduler last ran
+#[derive(Queryable, PartialEq, Clone)]
+pub struct ScheduleRun {
+    pub task: String,
+    pub last_run_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "schedule_runs"]
+pub struct NewScheduleRun<'a> {
+    pub task: &'a str,
+    pub last_run_at: NaiveDateTime,
+}
+
+/// A track queued to be posted at a set time
+#[derive(Queryable, PartialEq, Clone)]
+pub struct ScheduledPost {
+    pub id: i32,
+    pub track_id: i32,
+    /// When the track is due to be posted
+    pub scheduled_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "scheduled_posts"]
+pub struct NewScheduledPost {
+    pub track_id: i32,
+    pub scheduled_at: NaiveDateTime,
+}
+
+/// The outcome of posting a track to a destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostStatus {
+    /// The track was posted and counts as posted
+    Posted,
+    /// The destination rejected the post
+    Failed,
+    /// The track was posted here but a required destination failed, so the
+    /// track was left in the pool
+    Incomplete,
+    /// The post was deleted and the track put back in the pool
+    Undone,
+}
+
+impl PostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostStatus::Posted => "posted",
+            PostStatus::Failed => "failed",
+            PostStatus::Incomplete => "incomplete",
+            PostStatus::Undone => "undone",
+        }
+    }
+}
+
+#[derive(Queryable, PartialEq, Clone)]
+pub struct Post {
+    pub id: i32,
+    pub track_id: i32,
+    pub destination: String,
+    pub remote_id: Option<String>,
+    pub remote_url: Option<String>,
+    /// `None` for posts made before posts were recorded, which are older than
+    /// any other post
+    pub posted_at: Option<NaiveDateTime>,
+    pub status: String,
+    /// The playlist the track was picked from
+    pub playlist_id: Option<i32>,
+    /// The strategy that picked the track
+    pub strategy: Option<String>,
+    /// When the track was put back in the pool to be posted again
+    pub recycled_at: Option<NaiveDateTime>,
+    /// The post this one undid, only set for `undone` posts
+    pub undone_post_id: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[table_name = "posts"]
+pub struct NewPost<'a> {
+    pub track_id: i32,
+    pub destination: &'a str,
+    pub remote_id: Option<&'a str>,
+    pub remote_url: Option<&'a str>,
+    pub status: &'a str,
+    pub playlist_id: Option<i32>,
+    pub strategy: Option<&'a str>,
+    pub undone_post_id: Option<i32>,
+}
+
+/// How the track of a post was picked
+#[derive(Debug, Clone, Copy)]
+pub struct Selection<'a> {
+    /// The playlist the track was picked from
+    pub playlist_id: Option<i32>,
+    /// The strategy that picked the track
+    pub strategy: &'a str,
 }
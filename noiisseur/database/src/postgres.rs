@@ -0,0 +1,166 @@
+use diesel::pg::{upsert::excluded, Pg, PgConnection};
+
+use crate::{connection::ConnectionOptions, queries::queries};
+
+embed_migrations!("../../migrations/postgres");
+
+/// Connects to a database that has already been created and runs any pending
+/// migrations
+pub fn connect(db_url: &str, options: &ConnectionOptions) -> Result<PgConnection> {
+    let conn = PgConnection::establish(db_url)?;
+    // Postgres always enforces foreign keys and has no journal to configure,
+    // so only the timeout applies
+    conn.execute(&format!("SET lock_timeout = {}", options.busy_timeout_ms))?;
+    embedded_migrations::run(&conn)?;
+    Ok(conn)
+}
+
+/// A store backed by a Postgres database
+pub struct PgStore {
+    conn: PgConnection,
+}
+
+impl PgStore {
+    pub fn new(conn: PgConnection) -> Self {
+        PgStore { conn }
+    }
+
+    pub fn connect(db_url: &str, options: &ConnectionOptions) -> Result<Self> {
+        Ok(PgStore::new(connect(db_url, options)?))
+    }
+
+    pub fn connection(&self) -> &PgConnection {
+        &self.conn
+    }
+}
+
+/// Adds a track to a playlist. A track that was already in it has its
+/// membership replaced, which clears `removed_at`
+fn upsert_playlist_track(
+    conn: &PgConnection,
+    playlist_track: &NewPlaylistTrack,
+) -> QueryResult<usize> {
+    use crate::schema::playlist_tracks::columns::{
+        added_at, added_by, playlist_id, removed_at, track_id,
+    };
+
+    diesel::insert_into(playlist_tracks::table)
+        .values(playlist_track)
+        .on_conflict((playlist_id, track_id))
+        .do_update()
+        .set((
+            added_at.eq(excluded(added_at)),
+            added_by.eq(excluded(added_by)),
+            removed_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn)
+}
+
+fn upsert_playlist_sync(conn: &PgConnection, sync: &NewPlaylistSync) -> QueryResult<usize> {
+    use crate::schema::playlist_sync::columns::{
+        last_added_at, playlist_id, snapshot_id, synced_at,
+    };
+
+    diesel::insert_into(playlist_sync::table)
+        .values(sync)
+        .on_conflict(playlist_id)
+        .do_update()
+        .set((
+            snapshot_id.eq(excluded(snapshot_id)),
+            last_added_at.eq(excluded(last_added_at)),
+            synced_at.eq(excluded(synced_at)),
+        ))
+        .execute(conn)
+}
+
+fn upsert_schedule_run(conn: &PgConnection, run: &NewScheduleRun) -> QueryResult<usize> {
+    use crate::schema::schedule_runs::columns::{last_run_at, task};
+
+    diesel::insert_into(schedule_runs::table)
+        .values(run)
+        .on_conflict(task)
+        .do_update()
+        .set(last_run_at.eq(excluded(last_run_at)))
+        .execute(conn)
+}
+
+queries!(PgConnection, Pg, PgStore);
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::store::tests::store_tests;
+
+    /// Connects to the empty database at `DATABASE_URL`. Everything a test does
+    /// is rolled back once it's done, so the database stays empty
+    fn store() -> PgStore {
+        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set to a Postgres URL");
+        let store = PgStore::connect(&db_url, &ConnectionOptions::default()).unwrap();
+        store.connection().begin_test_transaction().unwrap();
+        store
+    }
+
+    store_tests!(store(), #[ignore]);
+
+    #[test]
+    #[ignore]
+    fn orders_posts_without_a_date_last() {
+        let store = store();
+        crate::store::tests::orders_posts_without_a_date_last(&store, |track_id| {
+            insert_legacy_post(store.connection(), track_id)
+        });
+    }
+
+    #[derive(QueryableByName, Debug, PartialEq)]
+    struct Column {
+        #[sql_type = "diesel::sql_types::Text"]
+        table_name: String,
+        #[sql_type = "diesel::sql_types::Text"]
+        column_name: String,
+    }
+
+    /// The Postgres and SQLite migrations are kept apart, so a column added to
+    /// one set and forgotten in the other would otherwise only show up as a
+    /// failing query
+    #[test]
+    #[ignore]
+    #[cfg(feature = "sqlite")]
+    fn migrations_create_the_same_columns_as_sqlite() {
+        let mut postgres = diesel::sql_query(
+            "SELECT table_name::text AS table_name, column_name::text AS column_name \
+             FROM information_schema.columns \
+             WHERE table_schema = current_schema() \
+             AND table_name <> '__diesel_schema_migrations'",
+        )
+        .load::<Column>(store().connection())
+        .unwrap();
+        let sqlite = crate::sqlite::connect(":memory:", &ConnectionOptions::default()).unwrap();
+        let mut sqlite = diesel::sql_query(
+            "SELECT m.name AS table_name, c.name AS column_name \
+             FROM sqlite_master m JOIN pragma_table_info(m.name) c \
+             WHERE m.type = 'table' AND m.name NOT LIKE 'sqlite_%' \
+             AND m.name <> '__diesel_schema_migrations'",
+        )
+        .load::<Column>(&sqlite)
+        .unwrap();
+
+        let key = |column: &Column| (column.table_name.clone(), column.column_name.clone());
+        postgres.sort_by_key(key);
+        sqlite.sort_by_key(key);
+        assert!(!postgres.is_empty());
+        assert_eq!(postgres, sqlite);
+    }
+
+    #[test]
+    #[ignore]
+    fn connects_to_postgres_urls() {
+        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set to a Postgres URL");
+
+        // Migrations that have already run are skipped
+        crate::connect(&db_url, &ConnectionOptions::default()).unwrap();
+        let store = crate::connect(&db_url, &ConnectionOptions::default()).unwrap();
+        assert!(store.get_playlists().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1101 @@
+/// Adds the queries shared by every backend to a backend module along with its
+/// `Store` impl. `$conn` is the module's connection type, `$db` its diesel
+/// backend and `$store` its store, while the module itself defines the upserts
+/// that differ between backends
+macro_rules! queries {
+    ($conn:ty, $db:ty, $store:ty) => {
+        type Conn = $conn;
+        type DB = $db;
+
+        use chrono::{NaiveDateTime, Utc};
+        use diesel::prelude::*;
+        use std::collections::{HashMap, HashSet};
+
+        use crate::{
+            added_since,
+            error::{Error, Result},
+            models::{
+                Album, Artist, NewAlbum, NewArtist, NewPlaylist, NewPlaylistSync,
+                NewPlaylistTrack, NewPost, NewScheduleRun, NewScheduledPost, NewTrack,
+                NewTrackArtist, NewTrackDetails, Playlist, PlaylistEntry, PlaylistSync, Post,
+                PostStatus, ScheduledPost, Selection, Track, TrackChanges, TrackDetails,
+            },
+            schema::{
+                albums, artists, playlist_sync, playlist_tracks, playlists, posts, schedule_runs,
+                scheduled_posts, track_artists, tracks,
+            },
+            store::Store,
+            Listing, PlaylistRemoval, SyncReport,
+        };
+
+        /// Stores a track fetched from Spotify along with its album and artists and
+        /// adds it to the playlist. A track that is already stored, e.g. because it's
+        /// in another playlist too, has its details refreshed and a track that was
+        /// removed from the playlist is added back. Returns the id of the track
+        pub fn save_track(
+            conn: &Conn,
+            playlist_id_val: i32,
+            details: &NewTrackDetails,
+        ) -> Result<i32> {
+            use crate::schema::tracks::columns::{id, spotify_id};
+
+            conn.transaction(|| {
+                let album_id = match &details.album {
+                    Some(album) => Some(save_album(conn, album)?),
+                    None => None,
+                };
+
+                let existing = tracks::table
+                    .filter(spotify_id.eq(details.spotify_id))
+                    .select(id)
+                    .first::<i32>(conn)
+                    .optional()?;
+
+                let track_id = match existing {
+                    Some(track_id) => {
+                        let changes = TrackChanges {
+                            name: details.name,
+                            url: details.url,
+                            album_id,
+                            duration_ms: details.duration_ms,
+                            explicit: details.explicit,
+                            popularity: details.popularity,
+                            isrc: details.isrc,
+                        };
+                        diesel::update(tracks::table.find(track_id))
+                            .set(&changes)
+                            .execute(conn)?;
+                        track_id
+                    }
+                    None => {
+                        let track = NewTrack {
+                            spotify_id: details.spotify_id,
+                            name: details.name,
+                            url: details.url,
+                            album_id,
+                            duration_ms: details.duration_ms,
+                            explicit: details.explicit,
+                            popularity: details.popularity,
+                            isrc: details.isrc,
+                        };
+                        diesel::insert_into(tracks::table)
+                            .values(&track)
+                            .execute(conn)?;
+                        tracks::table
+                            .filter(spotify_id.eq(details.spotify_id))
+                            .select(id)
+                            .first::<i32>(conn)?
+                    }
+                };
+
+                {
+                    use crate::schema::track_artists::columns::track_id as artist_track_id;
+                    diesel::delete(track_artists::table.filter(artist_track_id.eq(track_id)))
+                        .execute(conn)?;
+                }
+                for (position, artist) in details.artists.iter().enumerate() {
+                    let track_artist = NewTrackArtist {
+                        track_id,
+                        artist_id: save_artist(conn, artist)?,
+                        position: position as i32,
+                    };
+                    diesel::insert_into(track_artists::table)
+                        .values(&track_artist)
+                        .execute(conn)?;
+                }
+
+                let playlist_track = NewPlaylistTrack {
+                    playlist_id: playlist_id_val,
+                    track_id,
+                    added_at: details.added_at,
+                    added_by: details.added_by,
+                };
+                upsert_playlist_track(conn, &playlist_track)?;
+
+                Ok(track_id)
+            })
+        }
+
+        fn save_album(conn: &Conn, album: &NewAlbum) -> QueryResult<i32> {
+            use crate::schema::albums::columns::{id, spotify_id};
+
+            let existing = albums::table
+                .filter(spotify_id.eq(album.spotify_id))
+                .select(id)
+                .first::<i32>(conn)
+                .optional()?;
+
+            match existing {
+                Some(album_id) => {
+                    diesel::update(albums::table.find(album_id))
+                        .set(album)
+                        .execute(conn)?;
+                    Ok(album_id)
+                }
+                None => {
+                    diesel::insert_into(albums::table)
+                        .values(album)
+                        .execute(conn)?;
+                    albums::table
+                        .filter(spotify_id.eq(album.spotify_id))
+                        .select(id)
+                        .first::<i32>(conn)
+                }
+            }
+        }
+
+        fn save_artist(conn: &Conn, artist: &NewArtist) -> QueryResult<i32> {
+            use crate::schema::artists::columns::{id, name, spotify_id};
+
+            let existing = artists::table
+                .filter(spotify_id.eq(artist.spotify_id))
+                .select(id)
+                .first::<i32>(conn)
+                .optional()?;
+
+            match existing {
+                Some(artist_id) => {
+                    diesel::update(artists::table.find(artist_id))
+                        .set(name.eq(artist.name))
+                        .execute(conn)?;
+                    Ok(artist_id)
+                }
+                None => {
+                    diesel::insert_into(artists::table)
+                        .values(artist)
+                        .execute(conn)?;
+                    artists::table
+                        .filter(spotify_id.eq(artist.spotify_id))
+                        .select(id)
+                        .first::<i32>(conn)
+                }
+            }
+        }
+
+        pub fn delete_track(conn: &Conn, id: i32) -> Result<()> {
+            diesel::delete(tracks::table.find(id)).execute(conn)?;
+            Ok(())
+        }
+
+        /// Records a single attempt at posting a track to a destination
+        pub fn insert_post<'a>(
+            conn: &Conn,
+            track_id: i32,
+            destination: &'a str,
+            remote_id: Option<&'a str>,
+            remote_url: Option<&'a str>,
+            status: PostStatus,
+            selection: &Selection<'a>,
+        ) -> Result<usize> {
+            let post = NewPost {
+                track_id,
+                destination,
+                remote_id,
+                remote_url,
+                status: status.as_str(),
+                playlist_id: selection.playlist_id,
+                strategy: Some(selection.strategy),
+                undone_post_id: None,
+            };
+
+            diesel::insert_into(posts::table)
+                .values(&post)
+                .execute(conn)
+                .map_err(Error::from)
+        }
+
+        /// Records that a track was posted to a destination, taking it out of the
+        /// pool returned by `get_tracks`
+        pub fn mark_track_as_posted<'a>(
+            conn: &Conn,
+            track: &Track,
+            destination: &'a str,
+            remote_id: Option<&'a str>,
+            remote_url: Option<&'a str>,
+            selection: &Selection<'a>,
+        ) -> Result<usize> {
+            insert_post(
+                conn,
+                track.id,
+                destination,
+                remote_id,
+                remote_url,
+                PostStatus::Posted,
+                selection,
+            )
+        }
+
+        pub fn get_post(conn: &Conn, post_id: i32) -> Result<Option<Post>> {
+            posts::table
+                .find(post_id)
+                .first::<Post>(conn)
+                .optional()
+                .map_err(Error::from)
+        }
+
+        /// Gets the most recent post that is still live
+        pub fn get_last_post(conn: &Conn) -> Result<Option<Post>> {
+            use crate::schema::posts::columns::{id, posted_at};
+            // Posts without a date are the oldest, which Postgres would otherwise sort first
+            live_posts()
+                .order((posted_at.is_null(), posted_at.desc(), id.desc()))
+                .first::<Post>(conn)
+                .optional()
+                .map_err(Error::from)
+        }
+
+        /// Gets the posts that are still live from when a post's track was published,
+        /// one for each destination. Posts that were recycled together belong to the
+        /// same publish, as do the ones that haven't been recycled
+        pub fn get_published_posts(conn: &Conn, post: &Post) -> Result<Vec<Post>> {
+            use crate::schema::posts::columns::{id, recycled_at, track_id};
+
+            let mut query = live_posts().filter(track_id.eq(post.track_id));
+            query = match post.recycled_at {
+                Some(recycled_at_val) => query.filter(recycled_at.eq(recycled_at_val)),
+                None => query.filter(recycled_at.is_null()),
+            };
+            query
+                .order(id.asc())
+                .load::<Post>(conn)
+                .map_err(Error::from)
+        }
+
+        /// Gets the most recently posted tracks, most recent first, up to `limit`
+        /// tracks. A track that was posted to several destinations is only listed once
+        pub fn get_recently_posted_tracks(conn: &Conn, limit: usize) -> Result<Vec<Track>> {
+            use crate::schema::posts::columns::{id, posted_at, track_id};
+
+            let mut seen = HashSet::new();
+            let track_ids: Vec<i32> = live_posts()
+                .order((posted_at.is_null(), posted_at.desc(), id.desc()))
+                .select(track_id)
+                .load::<i32>(conn)?
+                .into_iter()
+                .filter(|track_id_val| seen.insert(*track_id_val))
+                .take(limit)
+                .collect();
+
+            let mut tracks = tracks::table
+                .filter(tracks::id.eq_any(&track_ids))
+                .load::<Track>(conn)?;
+            tracks.sort_by_key(|track| track_ids.iter().position(|id_val| *id_val == track.id));
+            Ok(tracks)
+        }
+
+        /// Puts posted tracks back in the pool so they can be posted again,
+        /// optionally only those in a playlist or last posted before a date. Their
+        /// posts are kept as history. Returns the number of tracks put back
+        pub fn reset_posted_tracks(
+            conn: &Conn,
+            playlist_id_val: Option<i32>,
+            posted_before: Option<NaiveDateTime>,
+        ) -> Result<usize> {
+            use crate::schema::posts::columns::{id, posted_at, recycled_at, status, track_id};
+
+            conn.transaction(|| {
+                let mut query = live_posts()
+                    .select((track_id, posted_at))
+                    .filter(recycled_at.is_null());
+                if let Some(playlist_id_val) = playlist_id_val {
+                    query = query.filter(track_id.eq_any(playlist_track_ids(playlist_id_val)));
+                }
+
+                // A track posted to several destinations is reset by when it was last
+                // posted. Posts without a date were made before any that have one
+                let mut last_posted: HashMap<i32, Option<NaiveDateTime>> = HashMap::new();
+                let posted = query.load::<(i32, Option<NaiveDateTime>)>(conn)?;
+                for (track_id_val, posted_at_val) in posted {
+                    let last = last_posted.entry(track_id_val).or_insert(posted_at_val);
+                    *last = (*last).max(posted_at_val);
+                }
+                let track_ids: Vec<i32> = last_posted
+                    .into_iter()
+                    .filter(|(_, last)| posted_before.is_none_or(|before| *last < Some(before)))
+                    .map(|(track_id_val, _)| track_id_val)
+                    .collect();
+
+                // Every posted track can be reset at once, which can be more than SQLite
+                // allows variables in a single query
+                let now = Utc::now().naive_utc();
+                for chunk in track_ids.chunks(500) {
+                    diesel::update(
+                        posts::table
+                            .filter(track_id.eq_any(chunk))
+                            .filter(status.eq(PostStatus::Posted.as_str()))
+                            .filter(id.nullable().ne_all(undone_post_ids()))
+                            .filter(recycled_at.is_null()),
+                    )
+                    .set(recycled_at.eq(now))
+                    .execute(conn)?;
+                }
+                Ok(track_ids.len())
+            })
+        }
+
+        /// Records that posts were undone, adding an `undone` post for each that
+        /// points at the post it undid. This reverses `mark_track_as_posted` so once
+        /// none of its posts are live, the track is returned by `get_tracks` again.
+        /// Either every post is undone or none are
+        pub fn undo_posts(conn: &Conn, undone: &[Post]) -> Result<usize> {
+            conn.transaction(|| {
+                let mut count = 0;
+                for post in undone {
+                    let undo = NewPost {
+                        track_id: post.track_id,
+                        destination: &post.destination,
+                        remote_id: post.remote_id.as_deref(),
+                        remote_url: post.remote_url.as_deref(),
+                        status: PostStatus::Undone.as_str(),
+                        playlist_id: post.playlist_id,
+                        strategy: post.strategy.as_deref(),
+                        undone_post_id: Some(post.id),
+                    };
+                    count += diesel::insert_into(posts::table)
+                        .values(&undo)
+                        .execute(conn)?;
+                }
+                Ok(count)
+            })
+        }
+
+        /// Gets the most recent posts along with the track and the playlists it's
+        /// still in. A track that is no longer in any playlist has none
+        pub fn get_post_history(
+            conn: &Conn,
+            since: Option<NaiveDateTime>,
+            playlist_id_val: Option<i32>,
+            limit: i64,
+        ) -> Result<Vec<(Post, Track, Vec<Playlist>)>> {
+            use crate::schema::posts::columns::{id, posted_at};
+
+            let mut query = posts::table.inner_join(tracks::table).into_boxed();
+            if let Some(since) = since {
+                query = query.filter(posted_at.ge(since));
+            }
+            if let Some(playlist_id_val) = playlist_id_val {
+                query = query.filter(tracks::id.eq_any(playlist_track_ids(playlist_id_val)));
+            }
+
+            let history = query
+                .order((posted_at.is_null(), posted_at.desc(), id.desc()))
+                .limit(limit)
+                .load::<(Post, Track)>(conn)?;
+
+            let track_ids: Vec<i32> = history.iter().map(|(_, track)| track.id).collect();
+            let memberships = {
+                use crate::schema::playlist_tracks::columns::{added_at, track_id};
+                playlist_tracks::table
+                    .inner_join(playlists::table)
+                    .filter(track_id.eq_any(track_ids))
+                    .order((added_at.asc(), playlists::id.asc()))
+                    .select((track_id, playlists::all_columns))
+                    .load::<(i32, Playlist)>(conn)?
+            };
+
+            let history = history
+                .into_iter()
+                .map(|(post, track)| {
+                    let playlists = memberships
+                        .iter()
+                        .filter(|(track_id, _)| *track_id == track.id)
+                        .map(|(_, playlist)| playlist.clone())
+                        .collect();
+                    (post, track, playlists)
+                })
+                .collect();
+
+            Ok(history)
+        }
+
+        pub fn get_track(conn: &Conn, track_id: i32) -> Result<Track> {
+            tracks::table
+                .find(track_id)
+                .first::<Track>(conn)
+                .map_err(Error::from)
+        }
+
+        /// Gets a track along with its playlists, album and artists
+        pub fn get_track_details(conn: &Conn, track: &Track) -> Result<TrackDetails> {
+            use crate::schema::track_artists::columns::{position, track_id};
+
+            let playlists = {
+                use crate::schema::playlist_tracks::columns::{added_at, removed_at, track_id};
+                playlist_tracks::table
+                    .inner_join(playlists::table)
+                    .filter(track_id.eq(track.id))
+                    .filter(removed_at.is_null())
+                    .order((added_at.asc(), playlists::id.asc()))
+                    .select(playlists::all_columns)
+                    .load::<Playlist>(conn)?
+            };
+            let album = match track.album_id {
+                Some(album_id) => albums::table
+                    .find(album_id)
+                    .first::<Album>(conn)
+                    .optional()?,
+                None => None,
+            };
+            let artists = track_artists::table
+                .inner_join(artists::table)
+                .filter(track_id.eq(track.id))
+                .order(position.asc())
+                .select(artists::all_columns)
+                .load::<Artist>(conn)?;
+
+            Ok(TrackDetails {
+                track: track.clone(),
+                playlists,
+                album,
+                artists,
+            })
+        }
+
+        /// Gets a track by its Spotify id
+        pub fn get_track_by_spotify_id(conn: &Conn, spotify_id_val: &str) -> Result<Option<Track>> {
+            use crate::schema::tracks::columns::spotify_id;
+            tracks::table
+                .filter(spotify_id.eq(spotify_id_val))
+                .first::<Track>(conn)
+                .optional()
+                .map_err(Error::from)
+        }
+
+        /// Gets the tracks that have not been posted yet and are still in a playlist
+        pub fn get_tracks(conn: &Conn) -> Result<Vec<Track>> {
+            use crate::schema::tracks::columns::id;
+            let tracks = tracks::table
+                .filter(id.ne_all(posted_track_ids()))
+                .filter(id.eq_any(listed_track_ids()))
+                .load::<Track>(conn)?;
+            Ok(tracks)
+        }
+
+        /// Whether a track has been posted and not put back in the pool since
+        pub fn is_track_posted(conn: &Conn, track_id_val: i32) -> Result<bool> {
+            use crate::schema::tracks::columns::id;
+            let posted = tracks::table
+                .filter(id.eq(track_id_val))
+                .filter(id.eq_any(posted_track_ids()))
+                .count()
+                .get_result::<i64>(conn)?;
+            Ok(posted > 0)
+        }
+
+        /// A subquery selecting the ids of every track that hasn't been removed from
+        /// all of its playlists
+        fn listed_track_ids(
+        ) -> playlist_tracks::BoxedQuery<'static, DB, diesel::sql_types::Integer> {
+            use crate::schema::playlist_tracks::columns::{removed_at, track_id};
+            playlist_tracks::table
+                .select(track_id)
+                .filter(removed_at.is_null())
+                .into_boxed()
+        }
+
+        /// The posts that are still live, which are the ones that were posted and
+        /// haven't been undone since
+        fn live_posts() -> posts::BoxedQuery<'static, DB> {
+            use crate::schema::posts::columns::{id, status};
+            posts::table
+                .filter(status.eq(PostStatus::Posted.as_str()))
+                .filter(id.nullable().ne_all(undone_post_ids()))
+                .into_boxed()
+        }
+
+        /// A subquery selecting the ids of every post that has been undone
+        fn undone_post_ids() -> posts::BoxedQuery<
+            'static,
+            DB,
+            diesel::sql_types::Nullable<diesel::sql_types::Integer>,
+        > {
+            use crate::schema::posts::columns::undone_post_id;
+            posts::table
+                .select(undone_post_id)
+                .filter(undone_post_id.is_not_null())
+                .into_boxed()
+        }
+
+        /// A subquery selecting the ids of every track that has been posted and not
+        /// put back in the pool since
+        fn posted_track_ids(
+        ) -> posts::BoxedQuery<'static, DB, diesel::sql_types::Integer> {
+            use crate::schema::posts::columns::{recycled_at, track_id};
+            live_posts()
+                .select(track_id)
+                .filter(recycled_at.is_null())
+        }
+
+        /// A subquery selecting the ids of every track that has ever been posted,
+        /// including tracks that were put back in the pool since
+        fn ever_posted_track_ids(
+        ) -> posts::BoxedQuery<'static, DB, diesel::sql_types::Integer> {
+            use crate::schema::posts::columns::track_id;
+            live_posts().select(track_id)
+        }
+
+        /// A subquery selecting the ids of every track in a playlist
+        fn playlist_track_ids(
+            playlist_id_val: i32,
+        ) -> playlist_tracks::BoxedQuery<'static, DB, diesel::sql_types::Integer> {
+            use crate::schema::playlist_tracks::columns::{playlist_id, track_id};
+            playlist_tracks::table
+                .select(track_id)
+                .filter(playlist_id.eq(playlist_id_val))
+                .into_boxed()
+        }
+
+        /// A subquery selecting the ids of every track in a playlist other than this one
+        fn other_playlist_track_ids(
+            playlist_id_val: i32,
+        ) -> playlist_tracks::BoxedQuery<'static, DB, diesel::sql_types::Integer> {
+            use crate::schema::playlist_tracks::columns::{playlist_id, track_id};
+            playlist_tracks::table
+                .select(track_id)
+                .filter(playlist_id.ne(playlist_id_val))
+                .into_boxed()
+        }
+
+        /// Adds a playlist, failing with `Error::Duplicate` if it has already been added
+        pub fn insert_playlist<'a>(
+            conn: &Conn,
+            name: &'a str,
+            spotify_id: &'a str,
+        ) -> Result<i32> {
+            use crate::schema::playlists::columns::id;
+
+            let playlist = NewPlaylist { name, spotify_id };
+
+            diesel::insert_into(playlists::table)
+                .values(&playlist)
+                .execute(conn)?;
+
+            let playlist_id = playlists::table
+                .select(id)
+                .order(id.desc())
+                .first::<i32>(conn)?;
+
+            Ok(playlist_id)
+        }
+
+        pub fn get_playlists(conn: &Conn) -> Result<Vec<Playlist>> {
+            let playlists = playlists::table.load::<Playlist>(conn)?;
+            Ok(playlists)
+        }
+
+        /// Sets how likely a playlist is to be picked relative to the others
+        pub fn set_playlist_weight(
+            conn: &Conn,
+            playlist_id_val: i32,
+            weight_val: i32,
+        ) -> Result<usize> {
+            use crate::schema::playlists::columns::weight;
+            diesel::update(playlists::table.find(playlist_id_val))
+                .set(weight.eq(weight_val))
+                .execute(conn)
+                .map_err(Error::from)
+        }
+
+        /// Gets every playlist along with its tracks that have not been posted yet
+        /// and are still in it. A track in several playlists is listed under each
+        pub fn get_tracks_by_playlist(conn: &Conn) -> Result<Vec<(Playlist, Vec<PlaylistEntry>)>> {
+            use crate::schema::playlist_tracks::columns::{added_at, playlist_id, removed_at};
+
+            let playlists = playlists::table
+                .order(playlists::id.asc())
+                .load::<Playlist>(conn)?;
+            let memberships = playlist_tracks::table
+                .inner_join(tracks::table)
+                .filter(removed_at.is_null())
+                .filter(tracks::id.ne_all(posted_track_ids()))
+                .order(tracks::id.asc())
+                .select((playlist_id, added_at, tracks::all_columns))
+                .load::<(i32, Option<NaiveDateTime>, Track)>(conn)?;
+            let credits = {
+                use crate::schema::track_artists::columns::{artist_id, position, track_id};
+                track_artists::table
+                    .filter(track_id.eq_any(listed_track_ids()))
+                    .filter(track_id.ne_all(posted_track_ids()))
+                    .order((track_id.asc(), position.asc()))
+                    .select((track_id, artist_id))
+                    .load::<(i32, i32)>(conn)?
+            };
+
+            let mut artist_ids: HashMap<i32, Vec<i32>> = HashMap::new();
+            for (track_id, artist_id) in credits {
+                artist_ids.entry(track_id).or_default().push(artist_id);
+            }
+            let mut grouped: HashMap<i32, Vec<PlaylistEntry>> = HashMap::new();
+            for (playlist_id_val, added, track) in memberships {
+                let entry = PlaylistEntry {
+                    artist_ids: artist_ids.get(&track.id).cloned().unwrap_or_default(),
+                    added_at: added,
+                    track,
+                };
+                grouped.entry(playlist_id_val).or_default().push(entry);
+            }
+            let tracks_by_playlist = playlists
+                .into_iter()
+                .map(|playlist| {
+                    let tracks = grouped.remove(&playlist.id).unwrap_or_default();
+                    (playlist, tracks)
+                })
+                .collect();
+
+            Ok(tracks_by_playlist)
+        }
+
+        /// Looks up a playlist by its Spotify id, falling back to its name
+        pub fn get_playlist(conn: &Conn, identifier: &str) -> Result<Option<Playlist>> {
+            use crate::schema::playlists::columns::{name, spotify_id};
+
+            let playlist = playlists::table
+                .filter(spotify_id.eq(identifier))
+                .first::<Playlist>(conn)
+                .optional()?;
+
+            match playlist {
+                Some(playlist) => Ok(Some(playlist)),
+                None => playlists::table
+                    .filter(name.eq(identifier))
+                    .first::<Playlist>(conn)
+                    .optional()
+                    .map_err(Error::from),
+            }
+        }
+
+        pub fn get_playlist_removal(
+            conn: &Conn,
+            playlist_id_val: i32,
+        ) -> Result<PlaylistRemoval> {
+            use crate::schema::tracks::columns::id;
+
+            let unposted = tracks::table
+                .filter(id.eq_any(playlist_track_ids(playlist_id_val)))
+                .filter(id.ne_all(other_playlist_track_ids(playlist_id_val)))
+                .filter(id.ne_all(ever_posted_track_ids()))
+                .count()
+                .get_result::<i64>(conn)?;
+            let posted = tracks::table
+                .filter(id.eq_any(playlist_track_ids(playlist_id_val)))
+                .filter(id.ne_all(other_playlist_track_ids(playlist_id_val)))
+                .filter(id.eq_any(ever_posted_track_ids()))
+                .count()
+                .get_result::<i64>(conn)?;
+            let shared = tracks::table
+                .filter(id.eq_any(playlist_track_ids(playlist_id_val)))
+                .filter(id.eq_any(other_playlist_track_ids(playlist_id_val)))
+                .count()
+                .get_result::<i64>(conn)?;
+
+            Ok(PlaylistRemoval {
+                unposted,
+                posted,
+                shared,
+            })
+        }
+
+        pub fn remove_playlist(conn: &Conn, playlist_id_val: i32) -> Result<PlaylistRemoval> {
+            conn.transaction(|| {
+                let removal = get_playlist_removal(conn, playlist_id_val)?;
+
+                {
+                    use crate::schema::tracks::columns::id;
+                    // Posted tracks are kept so we don't lose the history of what was posted
+                    diesel::delete(
+                        tracks::table
+                            .filter(id.eq_any(playlist_track_ids(playlist_id_val)))
+                            .filter(id.ne_all(other_playlist_track_ids(playlist_id_val)))
+                            .filter(id.ne_all(ever_posted_track_ids())),
+                    )
+                    .execute(conn)?;
+                }
+                {
+                    use crate::schema::playlist_tracks::columns::playlist_id;
+                    diesel::delete(playlist_tracks::table.filter(playlist_id.eq(playlist_id_val)))
+                        .execute(conn)?;
+                }
+                {
+                    use crate::schema::playlist_sync::columns::playlist_id;
+                    diesel::delete(playlist_sync::table.filter(playlist_id.eq(playlist_id_val)))
+                        .execute(conn)?;
+                }
+                diesel::delete(playlists::table.find(playlist_id_val)).execute(conn)?;
+
+                Ok(removal)
+            })
+        }
+
+        pub fn get_playlist_sync(
+            conn: &Conn,
+            playlist_id_val: i32,
+        ) -> Result<Option<PlaylistSync>> {
+            playlist_sync::table
+                .find(playlist_id_val)
+                .first::<PlaylistSync>(conn)
+                .optional()
+                .map_err(Error::from)
+        }
+
+        /// Syncs a playlist with every track currently in it on Spotify, in any order.
+        /// New tracks are stored, tracks that are no longer in the playlist are
+        /// marked as removed and tracks that were removed but have been added back are
+        /// restored. Tracks we already have that were added before the last seen
+        /// `added_at` are left as they are
+        pub fn sync_playlist(
+            conn: &Conn,
+            playlist_id_val: i32,
+            snapshot_id: &str,
+            tracks: &[NewTrackDetails],
+        ) -> Result<SyncReport> {
+            conn.transaction(|| {
+                let watermark =
+                    get_playlist_sync(conn, playlist_id_val)?.and_then(|sync| sync.last_added_at);
+                let mut listed = playlist_listings(conn, playlist_id_val)?;
+
+                let mut added = 0;
+                let mut track_ids = Vec::with_capacity(tracks.len());
+                for track in tracks.iter() {
+                    let track_id = match listed.get(track.spotify_id) {
+                        // Listings stored before their metadata was are refreshed too
+                        Some(listing)
+                            if !added_since(track, watermark) && !listing.is_stale(track) =>
+                        {
+                            listing.track_id
+                        }
+                        Some(_) => save_track(conn, playlist_id_val, track)?,
+                        None => {
+                            let track_id = save_track(conn, playlist_id_val, track)?;
+                            let listing = Listing::new(track_id, track);
+                            listed.insert(track.spotify_id.to_string(), listing);
+                            added += 1;
+                            track_id
+                        }
+                    };
+                    track_ids.push(track_id);
+                }
+
+                let removed = mark_removed_tracks(conn, playlist_id_val, &track_ids)?;
+
+                let sync = NewPlaylistSync {
+                    playlist_id: playlist_id_val,
+                    snapshot_id,
+                    last_added_at: tracks
+                        .iter()
+                        .filter_map(|track| track.added_at)
+                        .max()
+                        .max(watermark),
+                    synced_at: Utc::now().naive_utc(),
+                };
+                upsert_playlist_sync(conn, &sync)?;
+
+                Ok(SyncReport { added, removed })
+            })
+        }
+
+        /// The tracks listed in a playlist, by their Spotify id
+        fn playlist_listings(
+            conn: &Conn,
+            playlist_id_val: i32,
+        ) -> QueryResult<HashMap<String, Listing>> {
+            use crate::schema::playlist_tracks::columns::{added_at, playlist_id, removed_at};
+
+            let listings = playlist_tracks::table
+                .inner_join(tracks::table.left_join(albums::table))
+                .filter(playlist_id.eq(playlist_id_val))
+                .filter(removed_at.is_null())
+                .select((tracks::spotify_id, tracks::id, albums::spotify_id.nullable(), added_at))
+                .load::<(String, i32, Option<String>, Option<NaiveDateTime>)>(conn)?;
+            let credits = {
+                use crate::schema::track_artists::columns::{position, track_id};
+                track_artists::table
+                    .inner_join(artists::table)
+                    .filter(track_id.eq_any(playlist_track_ids(playlist_id_val)))
+                    .order((track_id.asc(), position.asc()))
+                    .select((track_id, artists::spotify_id))
+                    .load::<(i32, String)>(conn)?
+            };
+
+            let mut artists: HashMap<i32, Vec<String>> = HashMap::new();
+            for (track_id, artist) in credits {
+                artists.entry(track_id).or_default().push(artist);
+            }
+            let listings = listings
+                .into_iter()
+                .map(|(spotify_id, track_id, album, added_at_val)| {
+                    let listing = Listing {
+                        track_id,
+                        album,
+                        artists: artists.remove(&track_id).unwrap_or_default(),
+                        added_at: added_at_val,
+                    };
+                    (spotify_id, listing)
+                })
+                .collect();
+            Ok(listings)
+        }
+
+        /// Marks the tracks of a playlist that aren't in `track_ids` as removed from
+        /// it, so they're no longer returned by `get_tracks` unless they're in another
+        /// playlist. Returns the number of tracks marked as removed
+        fn mark_removed_tracks(
+            conn: &Conn,
+            playlist_id_val: i32,
+            track_ids: &[i32],
+        ) -> QueryResult<usize> {
+            use crate::schema::playlist_tracks::columns::{playlist_id, removed_at, track_id};
+
+            conn.transaction(|| {
+                let present: HashSet<&i32> = track_ids.iter().collect();
+                let removed: Vec<i32> = playlist_tracks::table
+                    .filter(playlist_id.eq(playlist_id_val))
+                    .filter(removed_at.is_null())
+                    .select(track_id)
+                    .load::<i32>(conn)?
+                    .into_iter()
+                    .filter(|id| !present.contains(id))
+                    .collect();
+
+                // Large playlists can have more removed tracks than SQLite allows
+                // variables in a single query
+                let now = Utc::now().naive_utc();
+                for chunk in removed.chunks(500) {
+                    diesel::update(
+                        playlist_tracks::table
+                            .filter(playlist_id.eq(playlist_id_val))
+                            .filter(track_id.eq_any(chunk)),
+                    )
+                    .set(removed_at.eq(now))
+                    .execute(conn)?;
+                }
+
+                Ok(removed.len())
+            })
+        }
+
+        /// Gets when a scheduled task last ran
+        pub fn get_last_run(conn: &Conn, task_val: &str) -> Result<Option<NaiveDateTime>> {
+            use crate::schema::schedule_runs::columns::last_run_at;
+            schedule_runs::table
+                .find(task_val)
+                .select(last_run_at)
+                .first::<NaiveDateTime>(conn)
+                .optional()
+                .map_err(Error::from)
+        }
+
+        /// Records when a scheduled task last ran
+        pub fn set_last_run(conn: &Conn, task: &str, last_run_at: NaiveDateTime) -> Result<usize> {
+            upsert_schedule_run(conn, &NewScheduleRun { task, last_run_at }).map_err(Error::from)
+        }
+
+        /// Queues a track to be posted at a set time. Returns the id of the queued post
+        pub fn insert_scheduled_post(
+            conn: &Conn,
+            track_id: i32,
+            scheduled_at: NaiveDateTime,
+        ) -> Result<i32> {
+            use crate::schema::scheduled_posts::columns::id;
+
+            conn.transaction(|| {
+                diesel::insert_into(scheduled_posts::table)
+                    .values(&NewScheduledPost {
+                        track_id,
+                        scheduled_at,
+                    })
+                    .execute(conn)?;
+
+                scheduled_posts::table
+                    .select(id)
+                    .order(id.desc())
+                    .first::<i32>(conn)
+                    .map_err(Error::from)
+            })
+        }
+
+        /// Gets every queued post along with its track, the first one due first
+        pub fn get_scheduled_posts(conn: &Conn) -> Result<Vec<(ScheduledPost, Track)>> {
+            use crate::schema::scheduled_posts::columns::{id, scheduled_at};
+            scheduled_posts::table
+                .inner_join(tracks::table)
+                .order((scheduled_at.asc(), id.asc()))
+                .load::<(ScheduledPost, Track)>(conn)
+                .map_err(Error::from)
+        }
+
+        pub fn delete_scheduled_post(conn: &Conn, scheduled_post_id: i32) -> Result<usize> {
+            diesel::delete(scheduled_posts::table.find(scheduled_post_id))
+                .execute(conn)
+                .map_err(Error::from)
+        }
+
+        /// Adds a post the way the migrations add the posts of tracks posted before
+        /// posts were recorded, without a date
+        #[cfg(test)]
+        fn insert_legacy_post(conn: &Conn, track_id_val: i32) {
+            use crate::schema::posts::columns::{destination, posted_at, status, track_id};
+            diesel::insert_into(posts::table)
+                .values((
+                    track_id.eq(track_id_val),
+                    destination.eq("twitter"),
+                    posted_at.eq(None::<NaiveDateTime>),
+                    status.eq(PostStatus::Posted.as_str()),
+                ))
+                .execute(conn)
+                .unwrap();
+        }
+
+        impl Store for $store {
+            fn save_track(&self, playlist_id: i32, details: &NewTrackDetails) -> Result<i32> {
+                save_track(&self.conn, playlist_id, details)
+            }
+
+            fn delete_track(&self, track_id: i32) -> Result<()> {
+                delete_track(&self.conn, track_id)
+            }
+
+            fn get_track(&self, track_id: i32) -> Result<Track> {
+                get_track(&self.conn, track_id)
+            }
+
+            fn get_track_by_spotify_id(&self, spotify_id: &str) -> Result<Option<Track>> {
+                get_track_by_spotify_id(&self.conn, spotify_id)
+            }
+
+            fn get_track_details(&self, track: &Track) -> Result<TrackDetails> {
+                get_track_details(&self.conn, track)
+            }
+
+            fn get_tracks(&self) -> Result<Vec<Track>> {
+                get_tracks(&self.conn)
+            }
+
+            fn is_track_posted(&self, track_id: i32) -> Result<bool> {
+                is_track_posted(&self.conn, track_id)
+            }
+
+            fn insert_post(
+                &self,
+                track_id: i32,
+                destination: &str,
+                remote_id: Option<&str>,
+                remote_url: Option<&str>,
+                status: PostStatus,
+                selection: &Selection,
+            ) -> Result<usize> {
+                insert_post(
+                    &self.conn,
+                    track_id,
+                    destination,
+                    remote_id,
+                    remote_url,
+                    status,
+                    selection,
+                )
+            }
+
+            fn get_post(&self, post_id: i32) -> Result<Option<Post>> {
+                get_post(&self.conn, post_id)
+            }
+
+            fn get_last_post(&self) -> Result<Option<Post>> {
+                get_last_post(&self.conn)
+            }
+
+            fn get_published_posts(&self, post: &Post) -> Result<Vec<Post>> {
+                get_published_posts(&self.conn, post)
+            }
+
+            fn get_recently_posted_tracks(&self, limit: usize) -> Result<Vec<Track>> {
+                get_recently_posted_tracks(&self.conn, limit)
+            }
+
+            fn reset_posted_tracks(
+                &self,
+                playlist_id: Option<i32>,
+                posted_before: Option<NaiveDateTime>,
+            ) -> Result<usize> {
+                reset_posted_tracks(&self.conn, playlist_id, posted_before)
+            }
+
+            fn undo_posts(&self, posts: &[Post]) -> Result<usize> {
+                undo_posts(&self.conn, posts)
+            }
+
+            fn get_post_history(
+                &self,
+                since: Option<NaiveDateTime>,
+                playlist_id: Option<i32>,
+                limit: i64,
+            ) -> Result<Vec<(Post, Track, Vec<Playlist>)>> {
+                get_post_history(&self.conn, since, playlist_id, limit)
+            }
+
+            fn insert_playlist(&self, name: &str, spotify_id: &str) -> Result<i32> {
+                insert_playlist(&self.conn, name, spotify_id)
+            }
+
+            fn get_playlists(&self) -> Result<Vec<Playlist>> {
+                get_playlists(&self.conn)
+            }
+
+            fn set_playlist_weight(&self, playlist_id: i32, weight: i32) -> Result<usize> {
+                set_playlist_weight(&self.conn, playlist_id, weight)
+            }
+
+            fn get_tracks_by_playlist(&self) -> Result<Vec<(Playlist, Vec<PlaylistEntry>)>> {
+                get_tracks_by_playlist(&self.conn)
+            }
+
+            fn get_playlist(&self, identifier: &str) -> Result<Option<Playlist>> {
+                get_playlist(&self.conn, identifier)
+            }
+
+            fn get_playlist_removal(&self, playlist_id: i32) -> Result<PlaylistRemoval> {
+                get_playlist_removal(&self.conn, playlist_id)
+            }
+
+            fn remove_playlist(&self, playlist_id: i32) -> Result<PlaylistRemoval> {
+                remove_playlist(&self.conn, playlist_id)
+            }
+
+            fn get_playlist_sync(&self, playlist_id: i32) -> Result<Option<PlaylistSync>> {
+                get_playlist_sync(&self.conn, playlist_id)
+            }
+
+            fn sync_playlist(
+                &self,
+                playlist_id: i32,
+                snapshot_id: &str,
+                tracks: &[NewTrackDetails],
+            ) -> Result<SyncReport> {
+                sync_playlist(&self.conn, playlist_id, snapshot_id, tracks)
+            }
+
+            fn get_last_run(&self, task: &str) -> Result<Option<NaiveDateTime>> {
+                get_last_run(&self.conn, task)
+            }
+
+            fn set_last_run(&self, task: &str, last_run_at: NaiveDateTime) -> Result<usize> {
+                set_last_run(&self.conn, task, last_run_at)
+            }
+
+            fn insert_scheduled_post(
+                &self,
+                track_id: i32,
+                scheduled_at: NaiveDateTime,
+            ) -> Result<i32> {
+                insert_scheduled_post(&self.conn, track_id, scheduled_at)
+            }
+
+            fn get_scheduled_posts(&self) -> Result<Vec<(ScheduledPost, Track)>> {
+                get_scheduled_posts(&self.conn)
+            }
+
+            fn delete_scheduled_post(&self, scheduled_post_id: i32) -> Result<usize> {
+                delete_scheduled_post(&self.conn, scheduled_post_id)
+            }
+        }
+    };
+}
+pub(crate) use queries;
@@ -1,8 +1,38 @@
 table! {
-    playlist_offset (id) {
+    albums (id) {
         id -> Integer,
+        spotify_id -> Text,
+        name -> Text,
+        release_date -> Nullable<Text>,
+        image_url -> Nullable<Text>,
+        thumbnail_url -> Nullable<Text>,
+    }
+}
+
+table! {
+    artists (id) {
+        id -> Integer,
+        spotify_id -> Text,
+        name -> Text,
+    }
+}
+
+table! {
+    playlist_sync (playlist_id) {
         playlist_id -> Integer,
-        offset -> Integer,
+        snapshot_id -> Text,
+        last_added_at -> Nullable<Timestamp>,
+        synced_at -> Timestamp,
+    }
+}
+
+table! {
+    playlist_tracks (playlist_id, track_id) {
+        playlist_id -> Integer,
+        track_id -> Integer,
+        added_at -> Nullable<Timestamp>,
+        added_by -> Nullable<Text>,
+        removed_at -> Nullable<Timestamp>,
     }
 }
 
@@ -11,6 +41,47 @@ table! {
         id -> Integer,
         spotify_id -> Text,
         name -> Text,
+        weight -> Integer,
+    }
+}
+
+table! {
+    posts (id) {
+        id -> Integer,
+        track_id -> Integer,
+        destination -> Text,
+        remote_id -> Nullable<Text>,
+        remote_url -> Nullable<Text>,
+        posted_at -> Nullable<Timestamp>,
+        status -> Text,
+        playlist_id -> Nullable<Integer>,
+        strategy -> Nullable<Text>,
+        recycled_at -> Nullable<Timestamp>,
+        undone_post_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    schedule_runs (task) {
+        task -> Text,
+        last_run_at -> Timestamp,
+    }
+}
+
+table! {
+    scheduled_posts (id) {
+        id -> Integer,
+        track_id -> Integer,
+        scheduled_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    track_artists (track_id, artist_id) {
+        track_id -> Integer,
+        artist_id -> Integer,
+        position -> Integer,
     }
 }
 
@@ -18,18 +89,35 @@ table! {
     tracks (id) {
         id -> Integer,
         spotify_id -> Text,
-        playlist_id -> Integer,
         name -> Text,
         url -> Text,
-        posted -> Integer,
+        album_id -> Nullable<Integer>,
+        duration_ms -> Nullable<Integer>,
+        explicit -> Nullable<Bool>,
+        popularity -> Nullable<Integer>,
+        isrc -> Nullable<Text>,
     }
 }
 
-joinable!(playlist_offset -> playlists (playlist_id));
-joinable!(tracks -> playlists (playlist_id));
+joinable!(playlist_sync -> playlists (playlist_id));
+joinable!(playlist_tracks -> playlists (playlist_id));
+joinable!(playlist_tracks -> tracks (track_id));
+joinable!(posts -> playlists (playlist_id));
+joinable!(posts -> tracks (track_id));
+joinable!(scheduled_posts -> tracks (track_id));
+joinable!(track_artists -> artists (artist_id));
+joinable!(track_artists -> tracks (track_id));
+joinable!(tracks -> albums (album_id));
 
 allow_tables_to_appear_in_same_query!(
-    playlist_offset,
+    albums,
+    artists,
+    playlist_sync,
+    playlist_tracks,
     playlists,
+    posts,
+    schedule_runs,
+    scheduled_posts,
+    track_artists,
     tracks,
 );
@@ -0,0 +1,79 @@
+use diesel::sqlite::{Sqlite, SqliteConnection};
+
+use crate::{
+    connection::{self, ConnectionOptions},
+    queries::queries,
+};
+
+embed_migrations!("../../migrations/sqlite");
+
+/// Connects to a database, creating it if it doesn't exist, and runs any
+/// pending migrations
+pub fn connect(db_url: &str, options: &ConnectionOptions) -> Result<SqliteConnection> {
+    let conn = SqliteConnection::establish(db_url)?;
+    connection::configure(&conn, options)?;
+    embedded_migrations::run(&conn)?;
+    connection::enforce_foreign_keys(&conn, options)?;
+    Ok(conn)
+}
+
+/// A store backed by a SQLite database
+pub struct SqliteStore {
+    conn: SqliteConnection,
+}
+
+impl SqliteStore {
+    pub fn new(conn: SqliteConnection) -> Self {
+        SqliteStore { conn }
+    }
+
+    pub fn connect(db_url: &str, options: &ConnectionOptions) -> Result<Self> {
+        Ok(SqliteStore::new(connect(db_url, options)?))
+    }
+
+    pub fn connection(&self) -> &SqliteConnection {
+        &self.conn
+    }
+}
+
+/// Adds a track to a playlist. A track that was already in it has its
+/// membership replaced, which clears `removed_at`
+fn upsert_playlist_track(
+    conn: &SqliteConnection,
+    playlist_track: &NewPlaylistTrack,
+) -> QueryResult<usize> {
+    diesel::replace_into(playlist_tracks::table)
+        .values(playlist_track)
+        .execute(conn)
+}
+
+fn upsert_playlist_sync(conn: &SqliteConnection, sync: &NewPlaylistSync) -> QueryResult<usize> {
+    diesel::replace_into(playlist_sync::table)
+        .values(sync)
+        .execute(conn)
+}
+
+fn upsert_schedule_run(conn: &SqliteConnection, run: &NewScheduleRun) -> QueryResult<usize> {
+    diesel::replace_into(schedule_runs::table)
+        .values(run)
+        .execute(conn)
+}
+
+queries!(SqliteConnection, Sqlite, SqliteStore);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::store_tests;
+
+    // Runs the same calls as the `MemoryStore` tests so the two are known to agree
+    store_tests!(SqliteStore::connect(":memory:", &ConnectionOptions::default()).unwrap());
+
+    #[test]
+    fn orders_posts_without_a_date_last() {
+        let store = SqliteStore::connect(":memory:", &ConnectionOptions::default()).unwrap();
+        crate::store::tests::orders_posts_without_a_date_last(&store, |track_id| {
+            insert_legacy_post(store.connection(), track_id)
+        });
+    }
+}
@@ -0,0 +1,582 @@
+use chrono::NaiveDateTime;
+
+use crate::{
+    error::Result,
+    models::{
+        NewTrackDetails, Playlist, PlaylistEntry, PlaylistSync, Post, PostStatus, ScheduledPost,
+        Selection, Track, TrackDetails,
+    },
+    PlaylistRemoval, SyncReport,
+};
+
+/// Where tracks, playlists and posts are kept. `SqliteStore` and `PgStore` keep
+/// them in a database while `MemoryStore` keeps everything in memory for tests
+pub trait Store {
+    /// Stores a track fetched from Spotify along with its album and artists
+    /// and adds it to the playlist. Returns the id of the track
+    fn save_track(&self, playlist_id: i32, details: &NewTrackDetails) -> Result<i32>;
+
+    fn delete_track(&self, track_id: i32) -> Result<()>;
+
+    fn get_track(&self, track_id: i32) -> Result<Track>;
+
+    fn get_track_by_spotify_id(&self, spotify_id: &str) -> Result<Option<Track>>;
+
+    /// Gets a track along with its playlists, album and artists
+    fn get_track_details(&self, track: &Track) -> Result<TrackDetails>;
+
+    /// Gets the tracks that have not been posted yet and are still in a playlist
+    fn get_tracks(&self) -> Result<Vec<Track>>;
+
+    /// Whether a track has been posted and not put back in the pool since
+    fn is_track_posted(&self, track_id: i32) -> Result<bool>;
+
+    /// Records a single attempt at posting a track to a destination
+    fn insert_post(
+        &self,
+        track_id: i32,
+        destination: &str,
+        remote_id: Option<&str>,
+        remote_url: Option<&str>,
+        status: PostStatus,
+        selection: &Selection,
+    ) -> Result<usize>;
+
+    /// Records that a track was posted to a destination, taking it out of the
+    /// pool returned by `get_tracks`
+    fn mark_track_as_posted(
+        &self,
+        track: &Track,
+        destination: &str,
+        remote_id: Option<&str>,
+        remote_url: Option<&str>,
+        selection: &Selection,
+    ) -> Result<usize> {
+        self.insert_post(
+            track.id,
+            destination,
+            remote_id,
+            remote_url,
+            PostStatus::Posted,
+            selection,
+        )
+    }
+
+    fn get_post(&self, post_id: i32) -> Result<Option<Post>>;
+
+    /// Gets the most recent post that is still live
+    fn get_last_post(&self) -> Result<Option<Post>>;
+
+    /// Gets the posts that are still live from when a post's track was
+    /// published, one for each destination, the given post included
+    fn get_published_posts(&self, post: &Post) -> Result<Vec<Post>>;
+
+    /// Gets the most recently posted tracks, most recent first. A track that
+    /// was posted to several destinations is only listed once
+    fn get_recently_posted_tracks(&self, limit: usize) -> Result<Vec<Track>>;
+
+    /// Puts posted tracks back in the pool so they can be posted again,
+    /// optionally only those in a playlist or last posted before a date.
+    /// Returns the number of tracks put back
+    fn reset_posted_tracks(
+        &self,
+        playlist_id: Option<i32>,
+        posted_before: Option<NaiveDateTime>,
+    ) -> Result<usize>;
+
+    /// Records that posts were undone with an `undone` post for each, all at
+    /// once or not at all. A track is put back in the pool once none of its
+    /// posts are live
+    fn undo_posts(&self, posts: &[Post]) -> Result<usize>;
+
+    /// Gets the most recent posts along with the track and the playlists it's
+    /// in, optionally only those since a date or from a playlist
+    fn get_post_history(
+        &self,
+        since: Option<NaiveDateTime>,
+        playlist_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<(Post, Track, Vec<Playlist>)>>;
+
+    /// Adds a playlist, failing with `Error::Duplicate` if it has already been added
+    fn insert_playlist(&self, name: &str, spotify_id: &str) -> Result<i32>;
+
+    fn get_playlists(&self) -> Result<Vec<Playlist>>;
+
+    /// Sets how likely a playlist is to be picked relative to the others
+    fn set_playlist_weight(&self, playlist_id: i32, weight: i32) -> Result<usize>;
+
+    /// Gets every playlist along with its tracks that have not been posted yet
+    /// and are still in it
+    fn get_tracks_by_playlist(&self) -> Result<Vec<(Playlist, Vec<PlaylistEntry>)>>;
+
+    /// Looks up a playlist by its Spotify id, falling back to its name
+    fn get_playlist(&self, identifier: &str) -> Result<Option<Playlist>>;
+
+    /// The tracks that would be affected by removing a playlist
+    fn get_playlist_removal(&self, playlist_id: i32) -> Result<PlaylistRemoval>;
+
+    /// Removes a playlist along with its unposted tracks
+    fn remove_playlist(&self, playlist_id: i32) -> Result<PlaylistRemoval>;
+
+    fn get_playlist_sync(&self, playlist_id: i32) -> Result<Option<PlaylistSync>>;
+
+    /// Syncs a playlist with every track currently in it on Spotify
+    fn sync_playlist(
+        &self,
+        playlist_id: i32,
+        snapshot_id: &str,
+        tracks: &[NewTrackDetails],
+    ) -> Result<SyncReport>;
+
+    /// Gets when a task run by the scheduler last ran
+    fn get_last_run(&self, task: &str) -> Result<Option<NaiveDateTime>>;
+
+    /// Records when a task run by the scheduler last ran
+    fn set_last_run(&self, task: &str, last_run_at: NaiveDateTime) -> Result<usize>;
+
+    /// Queues a track to be posted at a set time. Returns the id of the queued post
+    fn insert_scheduled_post(&self, track_id: i32, scheduled_at: NaiveDateTime) -> Result<i32>;
+
+    /// Gets every queued post along with its track, the first one due first
+    fn get_scheduled_posts(&self) -> Result<Vec<(ScheduledPost, Track)>>;
+
+    fn delete_scheduled_post(&self, scheduled_post_id: i32) -> Result<usize>;
+}
+
+/// The same calls made against every store, so the stores can be checked to
+/// behave the same. Each test gets a new, empty store
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{
+        models::{NewAlbum, NewArtist},
+        Error,
+    };
+
+    /// Adds a test for each of the calls below, run against the store `$store`
+    /// evaluates to. Any attributes after it are added to every test
+    macro_rules! store_tests {
+        ($store:expr $(, #[$attr:meta])*) => {
+            store_tests!(@tests [$(#[$attr])*] $store;
+                saves_tracks,
+                syncs_playlists,
+                removes_playlists,
+                posts_tracks,
+                resets_posted_tracks,
+                resets_many_posted_tracks,
+                undoes_every_post_of_a_publish,
+                queues_posts,
+                records_last_runs,
+            );
+        };
+        (@tests $attrs:tt $store:expr; $($name:ident,)*) => {
+            $(store_tests!(@test $attrs $store; $name);)*
+        };
+        (@test [$(#[$attr:meta])*] $store:expr; $name:ident) => {
+            #[test]
+            $(#[$attr])*
+            fn $name() {
+                let store = $store;
+                crate::store::tests::$name(&store);
+            }
+        };
+    }
+    pub(crate) use store_tests;
+
+    fn new_track<'a>(spotify_id: &'a str, name: &'a str) -> NewTrackDetails<'a> {
+        NewTrackDetails {
+            spotify_id,
+            name,
+            url: "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+            duration_ms: Some(263_000),
+            explicit: Some(false),
+            popularity: None,
+            isrc: None,
+            album: Some(NewAlbum {
+                spotify_id: "6VH2op0GKIl3WNTbZmmcmI",
+                name: "Mordechai",
+                release_date: Some("2020-06-26"),
+                image_url: None,
+                thumbnail_url: None,
+            }),
+            artists: vec![
+                NewArtist {
+                    spotify_id: "2mVVjNmdjXZZDvhgQWiakk",
+                    name: "Khruangbin",
+                },
+                NewArtist {
+                    spotify_id: "3qnGvpP8Yth1AqSBMqON5x",
+                    name: "Leon Bridges",
+                },
+            ],
+            added_at: None,
+            added_by: None,
+        }
+    }
+
+    fn selection(playlist_id: i32) -> Selection<'static> {
+        Selection {
+            playlist_id: Some(playlist_id),
+            strategy: "uniform",
+        }
+    }
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day)
+            .and_then(|date| date.and_hms_opt(9, 0, 0))
+            .unwrap()
+    }
+
+    fn track_names(tracks: Vec<Track>) -> Vec<String> {
+        let mut names: Vec<String> = tracks.into_iter().map(|t| t.name).collect();
+        names.sort();
+        names
+    }
+
+    fn pool(store: &dyn Store) -> Vec<String> {
+        track_names(store.get_tracks().unwrap())
+    }
+
+    pub fn saves_tracks(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let evening = store.insert_playlist("Evening", "evening").unwrap();
+
+        let id = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        // Saving a track again updates it and adds it to the other playlist
+        let saved_again = store
+            .save_track(evening, &new_track("time", "Time (You and I)"))
+            .unwrap();
+        assert_eq!(saved_again, id);
+
+        let track = store.get_track_by_spotify_id("time").unwrap().unwrap();
+        assert_eq!(track.id, id);
+        assert!(store.get_track_by_spotify_id("missing").unwrap().is_none());
+
+        let details = store.get_track_details(&track).unwrap();
+        assert_eq!(details.track.name, "Time (You and I)");
+        assert_eq!(details.artist_names(), "Khruangbin, Leon Bridges");
+        assert_eq!(details.album.as_ref().unwrap().name, "Mordechai");
+        let playlists: Vec<i32> = details.playlists.iter().map(|p| p.id).collect();
+        assert_eq!(playlists, vec![morning, evening]);
+
+        assert_eq!(pool(store), vec!["Time (You and I)"]);
+
+        // Last, as a failed statement aborts a Postgres transaction
+        assert!(matches!(
+            store.insert_playlist("Morning", "morning"),
+            Err(Error::Duplicate(_))
+        ));
+    }
+
+    pub fn syncs_playlists(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+
+        let tracks = [new_track("so-we-wont", "So We Won't Forget")];
+        let report = store.sync_playlist(morning, "snapshot-1", &tracks).unwrap();
+        assert_eq!((report.added, report.removed), (1, 1));
+        assert_eq!(pool(store), vec!["So We Won't Forget"]);
+        let sync = store.get_playlist_sync(morning).unwrap().unwrap();
+        assert_eq!(sync.snapshot_id, "snapshot-1");
+
+        // A track added back to the playlist is back in the pool
+        let tracks = [
+            new_track("so-we-wont", "So We Won't Forget"),
+            new_track("time", "Time"),
+        ];
+        let report = store.sync_playlist(morning, "snapshot-2", &tracks).unwrap();
+        assert_eq!((report.added, report.removed), (1, 0));
+        assert_eq!(pool(store), vec!["So We Won't Forget", "Time"]);
+
+        let by_playlist = store.get_tracks_by_playlist().unwrap();
+        assert_eq!(by_playlist.len(), 1);
+        assert_eq!(by_playlist[0].1.len(), 2);
+        assert_eq!(by_playlist[0].1[0].artist_ids.len(), 2);
+
+        // A listed track stored without its album and artists gets them
+        let pelota = store
+            .save_track(
+                morning,
+                &NewTrackDetails {
+                    album: None,
+                    artists: Vec::new(),
+                    ..new_track("pelota", "Pelota")
+                },
+            )
+            .unwrap();
+        let tracks = [
+            new_track("so-we-wont", "So We Won't Forget"),
+            new_track("time", "Time"),
+            new_track("pelota", "Pelota"),
+        ];
+        let report = store.sync_playlist(morning, "snapshot-3", &tracks).unwrap();
+        assert_eq!((report.added, report.removed), (0, 0));
+        let pelota = store.get_track(pelota).unwrap();
+        let details = store.get_track_details(&pelota).unwrap();
+        assert_eq!(details.album.as_ref().unwrap().name, "Mordechai");
+        assert_eq!(details.artist_names(), "Khruangbin, Leon Bridges");
+    }
+
+    pub fn removes_playlists(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let evening = store.insert_playlist("Evening", "evening").unwrap();
+        let time = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        store
+            .save_track(morning, &new_track("pelota", "Pelota"))
+            .unwrap();
+        store
+            .save_track(evening, &new_track("pelota", "Pelota"))
+            .unwrap();
+        store
+            .save_track(morning, &new_track("white-gloves", "White Gloves"))
+            .unwrap();
+        let time = store.get_track(time).unwrap();
+        store
+            .mark_track_as_posted(&time, "mastodon", None, None, &selection(morning))
+            .unwrap();
+
+        let removal = store.get_playlist_removal(morning).unwrap();
+        assert_eq!(
+            (removal.unposted, removal.posted, removal.shared),
+            (1, 1, 1)
+        );
+        assert_eq!(store.get_playlist("morning").unwrap().unwrap().id, morning);
+        assert_eq!(store.get_playlist("Evening").unwrap().unwrap().id, evening);
+
+        store.remove_playlist(morning).unwrap();
+        assert!(store.get_playlist("morning").unwrap().is_none());
+        assert_eq!(pool(store), vec!["Pelota"]);
+        // Posted tracks are kept as history
+        let history = store.get_post_history(None, None, 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].1.name, "Time");
+        assert!(history[0].2.is_empty());
+    }
+
+    pub fn posts_tracks(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let time = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        let pelota = store
+            .save_track(morning, &new_track("pelota", "Pelota"))
+            .unwrap();
+        let time = store.get_track(time).unwrap();
+
+        store
+            .insert_post(
+                pelota,
+                "mastodon",
+                None,
+                None,
+                PostStatus::Failed,
+                &selection(morning),
+            )
+            .unwrap();
+        store
+            .mark_track_as_posted(&time, "mastodon", Some("1"), None, &selection(morning))
+            .unwrap();
+        store
+            .mark_track_as_posted(&time, "bluesky", Some("2"), None, &selection(morning))
+            .unwrap();
+
+        // A failed post leaves its track in the pool
+        assert_eq!(pool(store), vec!["Pelota"]);
+        assert!(store.is_track_posted(time.id).unwrap());
+        assert!(!store.is_track_posted(pelota).unwrap());
+        let recent = store.get_recently_posted_tracks(5).unwrap();
+        assert_eq!(track_names(recent), vec!["Time"]);
+
+        let last = store.get_last_post().unwrap().unwrap();
+        assert_eq!(last.destination, "bluesky");
+        assert_eq!(last.remote_id.as_deref(), Some("2"));
+        assert_eq!(last.playlist_id, Some(morning));
+        assert_eq!(last.strategy.as_deref(), Some("uniform"));
+        assert_eq!(store.get_post(last.id).unwrap().unwrap().track_id, time.id);
+
+        let history = store.get_post_history(None, Some(morning), 10).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].2[0].id, morning);
+    }
+
+    pub fn resets_posted_tracks(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let evening = store.insert_playlist("Evening", "evening").unwrap();
+        let time = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        let pelota = store
+            .save_track(evening, &new_track("pelota", "Pelota"))
+            .unwrap();
+        for (id, playlist_id) in [(time, morning), (pelota, evening)].iter() {
+            let track = store.get_track(*id).unwrap();
+            store
+                .mark_track_as_posted(&track, "mastodon", None, None, &selection(*playlist_id))
+                .unwrap();
+        }
+        assert!(pool(store).is_empty());
+
+        // Both were posted just now
+        let reset = store.reset_posted_tracks(None, Some(date(1))).unwrap();
+        assert_eq!(reset, 0);
+        assert_eq!(store.reset_posted_tracks(Some(morning), None).unwrap(), 1);
+        assert_eq!(pool(store), vec!["Time"]);
+        assert!(!store.is_track_posted(time).unwrap());
+        assert!(store.is_track_posted(pelota).unwrap());
+        assert_eq!(store.reset_posted_tracks(None, None).unwrap(), 1);
+        assert_eq!(pool(store), vec!["Pelota", "Time"]);
+        assert_eq!(store.reset_posted_tracks(None, None).unwrap(), 0);
+    }
+
+    /// Not run against the `MemoryStore`, which has no posts without a date
+    pub fn orders_posts_without_a_date_last(store: &dyn Store, insert_legacy_post: impl Fn(i32)) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let time = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        let pelota = store
+            .save_track(morning, &new_track("pelota", "Pelota"))
+            .unwrap();
+        insert_legacy_post(time);
+        let track = store.get_track(pelota).unwrap();
+        store
+            .mark_track_as_posted(&track, "mastodon", None, None, &selection(morning))
+            .unwrap();
+
+        let last = store.get_last_post().unwrap().unwrap();
+        assert_eq!(last.track_id, pelota);
+        let history = store.get_post_history(None, None, 10).unwrap();
+        let dates: Vec<(i32, bool)> = history
+            .iter()
+            .map(|(post, _, _)| (post.track_id, post.posted_at.is_some()))
+            .collect();
+        assert_eq!(dates, vec![(pelota, true), (time, false)]);
+        let since = store.get_post_history(Some(date(1)), None, 10).unwrap();
+        assert_eq!(since.len(), 1);
+        let recent: Vec<i32> = store
+            .get_recently_posted_tracks(10)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(recent, vec![pelota, time]);
+
+        // A post without a date is older than any date
+        assert_eq!(store.reset_posted_tracks(None, Some(date(1))).unwrap(), 1);
+        assert_eq!(pool(store), vec!["Time"]);
+    }
+
+    pub fn resets_many_posted_tracks(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        for n in 0..1001 {
+            let spotify_id = format!("track-{}", n);
+            let id = store
+                .save_track(morning, &new_track(&spotify_id, "Time"))
+                .unwrap();
+            let track = store.get_track(id).unwrap();
+            store
+                .mark_track_as_posted(&track, "mastodon", None, None, &selection(morning))
+                .unwrap();
+        }
+
+        assert_eq!(store.reset_posted_tracks(None, None).unwrap(), 1001);
+        assert_eq!(store.get_tracks().unwrap().len(), 1001);
+    }
+
+    pub fn undoes_every_post_of_a_publish(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let time = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        let time = store.get_track(time).unwrap();
+        store
+            .mark_track_as_posted(&time, "mastodon", Some("1"), None, &selection(morning))
+            .unwrap();
+        store
+            .mark_track_as_posted(&time, "bluesky", Some("2"), None, &selection(morning))
+            .unwrap();
+        let first = store.get_last_post().unwrap().unwrap();
+        store.reset_posted_tracks(None, None).unwrap();
+        store
+            .mark_track_as_posted(&time, "mastodon", Some("3"), None, &selection(morning))
+            .unwrap();
+
+        // Posts from before the track was recycled belong to an earlier publish
+        let first = store.get_post(first.id).unwrap().unwrap();
+        let published = store.get_published_posts(&first).unwrap();
+        let remote_ids: Vec<_> = published.iter().map(|p| p.remote_id.clone()).collect();
+        assert_eq!(
+            remote_ids,
+            vec![Some("1".to_string()), Some("2".to_string())]
+        );
+
+        let last = store.get_last_post().unwrap().unwrap();
+        let published = store.get_published_posts(&last).unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].remote_id.as_deref(), Some("3"));
+
+        assert!(pool(store).is_empty());
+        assert_eq!(store.undo_posts(&published).unwrap(), 1);
+        assert_eq!(pool(store), vec!["Time"]);
+        assert!(store.get_published_posts(&last).unwrap().is_empty());
+
+        // The undo is a post of its own, the post it undid is kept as it was
+        let history = store.get_post_history(None, None, 10).unwrap();
+        let undo = &history[0].0;
+        assert_eq!(undo.status, PostStatus::Undone.as_str());
+        assert_eq!(undo.undone_post_id, Some(last.id));
+        assert_eq!(undo.remote_id.as_deref(), Some("3"));
+        let last = store.get_post(last.id).unwrap().unwrap();
+        assert_eq!(last.status, PostStatus::Posted.as_str());
+        assert_eq!(store.get_last_post().unwrap().unwrap().id, first.id);
+    }
+
+    pub fn queues_posts(store: &dyn Store) {
+        let morning = store.insert_playlist("Morning", "morning").unwrap();
+        let time = store
+            .save_track(morning, &new_track("time", "Time"))
+            .unwrap();
+        let pelota = store
+            .save_track(morning, &new_track("pelota", "Pelota"))
+            .unwrap();
+
+        let later = store.insert_scheduled_post(time, date(24)).unwrap();
+        let first = store.insert_scheduled_post(pelota, date(20)).unwrap();
+        let queued: Vec<(i32, i32)> = store
+            .get_scheduled_posts()
+            .unwrap()
+            .iter()
+            .map(|(scheduled, track)| (scheduled.id, track.id))
+            .collect();
+        assert_eq!(queued, vec![(first, pelota), (later, time)]);
+
+        assert_eq!(store.delete_scheduled_post(first).unwrap(), 1);
+        assert_eq!(store.delete_scheduled_post(first).unwrap(), 0);
+        // Removing the playlist deletes its unposted tracks along with their queued posts
+        store.remove_playlist(morning).unwrap();
+        assert!(store.get_scheduled_posts().unwrap().is_empty());
+
+        // Last, as a failed statement aborts a Postgres transaction
+        assert!(store.insert_scheduled_post(time, date(24)).is_err());
+    }
+
+    pub fn records_last_runs(store: &dyn Store) {
+        assert_eq!(store.get_last_run("post").unwrap(), None);
+
+        store.set_last_run("post", date(20)).unwrap();
+        store.set_last_run("post", date(21)).unwrap();
+        store.set_last_run("update", date(20)).unwrap();
+
+        assert_eq!(store.get_last_run("post").unwrap(), Some(date(21)));
+        assert_eq!(store.get_last_run("update").unwrap(), Some(date(20)));
+    }
+}
@@ -0,0 +1,332 @@
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use reqwest::blocking::{Client, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use database::models::TrackDetails;
+
+use crate::{error::Error, PublishReceipt, Publisher, TextLimit};
+
+const BLUESKY_SERVICE_URL: &str = "https://bsky.social";
+const BLUESKY_APP_URL: &str = "https://bsky.app";
+const MAX_POST_LENGTH: usize = 300;
+
+fn default_service_url() -> String {
+    BLUESKY_SERVICE_URL.to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlueskyConfig {
+    /// The base url of the PDS to log in to. Defaults to `https://bsky.social`
+    #[serde(default = "default_service_url")]
+    pub service_url: String,
+    /// The handle or DID of the account
+    pub identifier: String,
+    /// An app password created under *Settings > App Passwords*
+    pub app_password: String,
+}
+
+#[derive(Serialize)]
+struct CreateSession<'a> {
+    identifier: &'a str,
+    password: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    access_jwt: String,
+    did: String,
+    handle: String,
+}
+
+#[derive(Deserialize)]
+struct CreatedRecord {
+    uri: String,
+}
+
+/// Posts tracks to Bluesky through the AT Protocol XRPC API
+pub struct Bluesky {
+    config: BlueskyConfig,
+    client: Client,
+}
+
+impl Bluesky {
+    pub fn new(config: BlueskyConfig) -> Self {
+        Bluesky {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    fn xrpc_url(&self, method: &str) -> String {
+        format!(
+            "{service_url}/xrpc/{method}",
+            service_url = self.config.service_url.trim_end_matches('/'),
+            method = method
+        )
+    }
+
+    fn check(&self, response: Response) -> Result<Response> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        Err(Error::Rejected {
+            destination: self.name().to_string(),
+            status: response.status().as_u16(),
+            body: response.text()?,
+        }
+        .into())
+    }
+
+    fn create_session(&self) -> Result<Session> {
+        let request = CreateSession {
+            identifier: &self.config.identifier,
+            password: &self.config.app_password,
+        };
+        let response = self
+            .client
+            .post(&self.xrpc_url("com.atproto.server.createSession"))
+            .json(&request)
+            .send()?;
+
+        let session = self.check(response)?.json()?;
+        Ok(session)
+    }
+}
+
+impl Publisher for Bluesky {
+    fn name(&self) -> &str {
+        "bluesky"
+    }
+
+    fn publish(&self, track: &TrackDetails, text: &str) -> Result<PublishReceipt> {
+        let session = self.create_session()?;
+
+        // Links aren't detected by Bluesky, they're marked with a facet. Facets
+        // index into the UTF-8 bytes of the text, not its characters
+        let url = &track.track.url;
+        let facets = match text.find(url) {
+            Some(link_start) => json!([{
+                "index": { "byteStart": link_start, "byteEnd": link_start + url.len() },
+                "features": [{
+                    "$type": "app.bsky.richtext.facet#link",
+                    "uri": url,
+                }],
+            }]),
+            None => json!([]),
+        };
+
+        let record = json!({
+            "$type": "app.bsky.feed.post",
+            "text": text,
+            "createdAt": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            "facets": facets,
+            "embed": {
+                "$type": "app.bsky.embed.external",
+                "external": {
+                    "uri": url,
+                    "title": track.track.name,
+                    "description": track.artist_names(),
+                },
+            },
+        });
+        let request = json!({
+            "repo": session.did,
+            "collection": "app.bsky.feed.post",
+            "record": record,
+        });
+
+        let response = self
+            .client
+            .post(&self.xrpc_url("com.atproto.repo.createRecord"))
+            .bearer_auth(&session.access_jwt)
+            .json(&request)
+            .send()?;
+        let created: CreatedRecord = self.check(response)?.json()?;
+
+        // The record key is the last segment of the `at://` uri
+        let rkey = created.uri.rsplit('/').next().unwrap_or_default();
+        let url = format!(
+            "{app_url}/profile/{handle}/post/{rkey}",
+            app_url = BLUESKY_APP_URL,
+            handle = session.handle,
+            rkey = rkey
+        );
+
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: Some(created.uri),
+            remote_url: Some(url),
+        })
+    }
+
+    fn text_limit(&self) -> Option<TextLimit> {
+        Some(TextLimit {
+            max_length: MAX_POST_LENGTH,
+            url_length: None,
+        })
+    }
+
+    fn unpublish(&self, remote_id: &str) -> Result<()> {
+        let session = self.create_session()?;
+
+        // The remote id is the record's `at://<did>/<collection>/<rkey>` uri
+        let rkey = remote_id.rsplit('/').next().unwrap_or_default();
+        let request = json!({
+            "repo": session.did,
+            "collection": "app.bsky.feed.post",
+            "rkey": rkey,
+        });
+
+        let response = self
+            .client
+            .post(&self.xrpc_url("com.atproto.repo.deleteRecord"))
+            .bearer_auth(&session.access_jwt)
+            .json(&request)
+            .send()?;
+
+        self.check(response)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{mock, Matcher, Mock};
+
+    use super::*;
+    use crate::tests::track_details;
+
+    fn bluesky() -> Bluesky {
+        Bluesky::new(BlueskyConfig {
+            service_url: mockito::server_url(),
+            identifier: "noiisseur.bsky.social".to_string(),
+            app_password: "app-password".to_string(),
+        })
+    }
+
+    fn create_session() -> Mock {
+        mock("POST", "/xrpc/com.atproto.server.createSession")
+            .match_body(Matcher::Json(json!({
+                "identifier": "noiisseur.bsky.social",
+                "password": "app-password",
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"accessJwt": "jwt", "did": "did:plc:abc", "handle": "noiisseur.bsky.social"}"#,
+            )
+            .create()
+    }
+
+    #[test]
+    fn publishes_a_post_with_a_link_facet_and_embed() {
+        let track = track_details();
+        // The facet counts bytes, so the multibyte characters before the link
+        // put it further along than its character offset
+        let text = format!("Café — {}", track.track.url);
+        let session = create_session();
+        let record = mock("POST", "/xrpc/com.atproto.repo.createRecord")
+            .match_header("authorization", "Bearer jwt")
+            .match_body(Matcher::PartialJson(json!({
+                "repo": "did:plc:abc",
+                "collection": "app.bsky.feed.post",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "text": text,
+                    "facets": [{
+                        "index": { "byteStart": 10, "byteEnd": 10 + track.track.url.len() },
+                        "features": [{
+                            "$type": "app.bsky.richtext.facet#link",
+                            "uri": track.track.url,
+                        }],
+                    }],
+                    "embed": {
+                        "$type": "app.bsky.embed.external",
+                        "external": {
+                            "uri": track.track.url,
+                            "title": "Time (You and I)",
+                            "description": "Khruangbin",
+                        },
+                    },
+                },
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"uri": "at://did:plc:abc/app.bsky.feed.post/3k2a", "cid": "cid"}"#)
+            .create();
+
+        let receipt = bluesky().publish(&track, &text).unwrap();
+
+        session.assert();
+        record.assert();
+        assert_eq!(receipt.destination, "bluesky");
+        assert_eq!(
+            receipt.remote_id.as_deref(),
+            Some("at://did:plc:abc/app.bsky.feed.post/3k2a")
+        );
+        assert_eq!(
+            receipt.remote_url.as_deref(),
+            Some("https://bsky.app/profile/noiisseur.bsky.social/post/3k2a")
+        );
+    }
+
+    #[test]
+    fn publishes_without_a_facet_when_the_link_is_missing() {
+        let session = create_session();
+        let record = mock("POST", "/xrpc/com.atproto.repo.createRecord")
+            .match_body(Matcher::PartialJson(json!({
+                "record": { "text": "Time (You and I)", "facets": [] },
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"uri": "at://did:plc:abc/app.bsky.feed.post/3k2a", "cid": "cid"}"#)
+            .create();
+
+        bluesky()
+            .publish(&track_details(), "Time (You and I)")
+            .unwrap();
+
+        session.assert();
+        record.assert();
+    }
+
+    #[test]
+    fn reports_a_failed_login() {
+        let session = mock("POST", "/xrpc/com.atproto.server.createSession")
+            .with_status(401)
+            .with_body(r#"{"error": "AuthenticationRequired"}"#)
+            .create();
+
+        let error = bluesky()
+            .publish(&track_details(), "Time (You and I)")
+            .unwrap_err();
+
+        session.assert();
+        match error.downcast_ref::<Error>() {
+            Some(Error::Rejected { status, .. }) => assert_eq!(*status, 401),
+            _ => panic!("expected the login to be rejected, got {}", error),
+        }
+    }
+
+    #[test]
+    fn deletes_a_post_by_its_record_key() {
+        let session = create_session();
+        let delete = mock("POST", "/xrpc/com.atproto.repo.deleteRecord")
+            .match_header("authorization", "Bearer jwt")
+            .match_body(Matcher::Json(json!({
+                "repo": "did:plc:abc",
+                "collection": "app.bsky.feed.post",
+                "rkey": "3k2a",
+            })))
+            .with_body("{}")
+            .create();
+
+        bluesky()
+            .unpublish("at://did:plc:abc/app.bsky.feed.post/3k2a")
+            .unwrap();
+
+        session.assert();
+        delete.assert();
+    }
+}
@@ -0,0 +1,17 @@
+use thiserror;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid OAuth state parameter")]
+    InvalidOAuthState,
+    #[error("Missing environment variable: {0}")]
+    MissingVariable(&'static str),
+    #[error("{destination} rejected the post with status {status}: {body}")]
+    Rejected {
+        destination: String,
+        status: u16,
+        body: String,
+    },
+    #[error("{0} does not support deleting posts")]
+    Unsupported(String),
+}
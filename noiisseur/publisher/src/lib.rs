@@ -0,0 +1,125 @@
+pub mod bluesky;
+pub mod error;
+pub mod mastodon;
+pub mod template;
+pub mod twitter;
+pub mod webhook;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use database::models::TrackDetails;
+
+use error::Error;
+
+pub use bluesky::{Bluesky, BlueskyConfig};
+pub use mastodon::{Mastodon, MastodonConfig};
+pub use template::TextLimit;
+pub use twitter::{ApiVersion, Credentials, Twitter, TwitterConfig};
+pub use webhook::{Discord, DiscordConfig, Slack, SlackConfig, Webhook, WebhookConfig};
+
+/// Details about a post once it has been published to a destination
+#[derive(Debug, Clone)]
+pub struct PublishReceipt {
+    pub destination: String,
+    pub remote_id: Option<String>,
+    pub remote_url: Option<String>,
+}
+
+/// A destination tracks can be posted to
+pub trait Publisher {
+    /// The name of the destination e.g. `twitter`
+    fn name(&self) -> &str;
+
+    /// Posts the track with the rendered text, returning a receipt for the published post
+    fn publish(&self, track: &TrackDetails, text: &str) -> Result<PublishReceipt>;
+
+    /// The longest post the destination accepts, if it has a limit
+    fn text_limit(&self) -> Option<TextLimit> {
+        None
+    }
+
+    /// Deletes a published post using the `remote_id` from its receipt
+    fn unpublish(&self, _remote_id: &str) -> Result<()> {
+        Err(Error::Unsupported(self.name().to_string()).into())
+    }
+}
+
+/// The configuration of a single destination, tagged by its `kind`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PublisherConfig {
+    Twitter(TwitterConfig),
+    Mastodon(MastodonConfig),
+    Bluesky(BlueskyConfig),
+    Discord(DiscordConfig),
+    Slack(SlackConfig),
+    Webhook(WebhookConfig),
+}
+
+impl PublisherConfig {
+    /// The name of the publisher built from this config
+    pub fn name(&self) -> &str {
+        match self {
+            PublisherConfig::Twitter(_) => "twitter",
+            PublisherConfig::Mastodon(_) => "mastodon",
+            PublisherConfig::Bluesky(_) => "bluesky",
+            PublisherConfig::Discord(_) => "discord",
+            PublisherConfig::Slack(_) => "slack",
+            PublisherConfig::Webhook(_) => "webhook",
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Publisher>> {
+        let publisher: Box<dyn Publisher> = match self {
+            PublisherConfig::Twitter(config) => Box::new(Twitter::new(config.clone())?),
+            PublisherConfig::Mastodon(config) => Box::new(Mastodon::new(config.clone())),
+            PublisherConfig::Bluesky(config) => Box::new(Bluesky::new(config.clone())),
+            PublisherConfig::Discord(config) => Box::new(Discord::new(config.clone())),
+            PublisherConfig::Slack(config) => Box::new(Slack::new(config.clone())),
+            PublisherConfig::Webhook(config) => Box::new(Webhook::new(config.clone())),
+        };
+        Ok(publisher)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use database::models::{Album, Artist, Playlist, Track, TrackDetails};
+
+    /// A track in a single playlist, as it would be loaded from the database
+    pub fn track_details() -> TrackDetails {
+        TrackDetails {
+            track: Track {
+                id: 1,
+                spotify_id: "4uLU6hMCjMI75M1A2tKUQC".to_string(),
+                name: "Time (You and I)".to_string(),
+                url: "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC".to_string(),
+                album_id: Some(1),
+                duration_ms: Some(263_000),
+                explicit: Some(false),
+                popularity: None,
+                isrc: None,
+            },
+            playlists: vec![Playlist {
+                id: 1,
+                spotify_id: "37i9dQZF1DX4WYpdgoIcn6".to_string(),
+                name: "Coffee in the Morning".to_string(),
+                weight: 1,
+            }],
+            album: Some(Album {
+                id: 1,
+                spotify_id: "6VH2op0GKIl3WNTbZmmcmI".to_string(),
+                name: "Mordechai".to_string(),
+                release_date: None,
+                image_url: None,
+                thumbnail_url: Some("https://i.scdn.co/image/thumbnail".to_string()),
+            }),
+            artists: vec![Artist {
+                id: 1,
+                spotify_id: "2mVVjNmdjXZZDvhgQWiakk".to_string(),
+                name: "Khruangbin".to_string(),
+            }],
+        }
+    }
+}
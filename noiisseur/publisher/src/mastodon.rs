@@ -0,0 +1,178 @@
+use anyhow::Result;
+use reqwest::blocking::{Client, Response};
+use serde::Deserialize;
+
+use database::models::TrackDetails;
+
+use crate::{error::Error, PublishReceipt, Publisher, TextLimit};
+
+const MAX_STATUS_LENGTH: usize = 500;
+// Links count as 23 characters no matter how long they are
+const STATUS_URL_LENGTH: usize = 23;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MastodonConfig {
+    /// The base url of the instance e.g. `https://mastodon.social`
+    pub instance_url: String,
+    pub access_token: String,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    id: String,
+    url: Option<String>,
+}
+
+/// Posts tracks as statuses to a Mastodon instance
+pub struct Mastodon {
+    config: MastodonConfig,
+    client: Client,
+}
+
+impl Mastodon {
+    pub fn new(config: MastodonConfig) -> Self {
+        Mastodon {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    fn statuses_url(&self) -> String {
+        format!(
+            "{instance_url}/api/v1/statuses",
+            instance_url = self.config.instance_url.trim_end_matches('/')
+        )
+    }
+
+    fn check(&self, response: Response) -> Result<Response> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        Err(Error::Rejected {
+            destination: self.name().to_string(),
+            status: response.status().as_u16(),
+            body: response.text()?,
+        }
+        .into())
+    }
+}
+
+impl Publisher for Mastodon {
+    fn name(&self) -> &str {
+        "mastodon"
+    }
+
+    fn publish(&self, _track: &TrackDetails, text: &str) -> Result<PublishReceipt> {
+        let response = self
+            .client
+            .post(&self.statuses_url())
+            .bearer_auth(&self.config.access_token)
+            .form(&[("status", text)])
+            .send()?;
+
+        let status: Status = self.check(response)?.json()?;
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: Some(status.id),
+            remote_url: status.url,
+        })
+    }
+
+    fn text_limit(&self) -> Option<TextLimit> {
+        Some(TextLimit {
+            max_length: MAX_STATUS_LENGTH,
+            url_length: Some(STATUS_URL_LENGTH),
+        })
+    }
+
+    fn unpublish(&self, remote_id: &str) -> Result<()> {
+        let status_url = format!("{}/{}", self.statuses_url(), remote_id);
+        let response = self
+            .client
+            .delete(&status_url)
+            .bearer_auth(&self.config.access_token)
+            .send()?;
+
+        self.check(response)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{mock, Matcher};
+
+    use super::*;
+    use crate::tests::track_details;
+
+    fn mastodon() -> Mastodon {
+        Mastodon::new(MastodonConfig {
+            // A trailing slash is allowed on the instance url
+            instance_url: format!("{}/", mockito::server_url()),
+            access_token: "token".to_string(),
+        })
+    }
+
+    #[test]
+    fn publishes_a_status() {
+        let status = mock("POST", "/api/v1/statuses")
+            .match_header("authorization", "Bearer token")
+            .match_body(Matcher::UrlEncoded(
+                "status".to_string(),
+                "Time (You and I) by Khruangbin".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": "108", "url": "https://mastodon.social/@noiisseur/108"}"#)
+            .create();
+
+        let receipt = mastodon()
+            .publish(&track_details(), "Time (You and I) by Khruangbin")
+            .unwrap();
+
+        status.assert();
+        assert_eq!(receipt.destination, "mastodon");
+        assert_eq!(receipt.remote_id.as_deref(), Some("108"));
+        assert_eq!(
+            receipt.remote_url.as_deref(),
+            Some("https://mastodon.social/@noiisseur/108")
+        );
+    }
+
+    #[test]
+    fn reports_a_rejected_status() {
+        let status = mock("POST", "/api/v1/statuses")
+            .with_status(422)
+            .with_body(r#"{"error": "Validation failed"}"#)
+            .create();
+
+        let error = mastodon()
+            .publish(&track_details(), "Time (You and I)")
+            .unwrap_err();
+
+        status.assert();
+        match error.downcast_ref::<Error>() {
+            Some(Error::Rejected {
+                destination,
+                status,
+                ..
+            }) => {
+                assert_eq!(destination, "mastodon");
+                assert_eq!(*status, 422);
+            }
+            _ => panic!("expected the status to be rejected, got {}", error),
+        }
+    }
+
+    #[test]
+    fn deletes_a_status() {
+        let delete = mock("DELETE", "/api/v1/statuses/108")
+            .match_header("authorization", "Bearer token")
+            .with_body("{}")
+            .create();
+
+        mastodon().unpublish("108").unwrap();
+
+        delete.assert();
+    }
+}
@@ -0,0 +1,229 @@
+use database::models::TrackDetails;
+
+/// The template used when none is configured, which posts just the link
+pub const DEFAULT_TEMPLATE: &str = "{url}";
+
+const ELLIPSIS: char = '…';
+
+/// The maximum length of a post at a destination
+#[derive(Debug, Clone, Copy)]
+pub struct TextLimit {
+    pub max_length: usize,
+    /// Some destinations count every link as the same length, regardless of
+    /// how long the link really is
+    pub url_length: Option<usize>,
+}
+
+/// Renders a post from a template. The placeholders `{name}`, `{artists}`,
+/// `{album}`, `{url}`, `{playlist}` and `{playlist_tag}` are replaced with the
+/// track's values. A track in several playlists uses the first it was added to
+pub fn render(template: &str, track: &TrackDetails) -> String {
+    let playlist_name = track
+        .playlists
+        .first()
+        .map(|p| p.name.as_str())
+        .unwrap_or_default();
+    let album = track.album.as_ref().map(|a| a.name.as_str());
+    // Hashtags can't contain spaces or punctuation e.g. "Coffee in the Morning" -> "CoffeeInTheMorning"
+    let playlist_tag: String = playlist_name
+        .split(|c: char| !c.is_alphanumeric())
+        .map(capitalize)
+        .collect();
+
+    let text = substitute(template, |placeholder| {
+        let value = match placeholder {
+            "name" => track.track.name.clone(),
+            "artists" => track.artist_names(),
+            "album" => album.unwrap_or_default().to_string(),
+            "playlist_tag" => playlist_tag.clone(),
+            "playlist" => playlist_name.to_string(),
+            "url" => track.track.url.clone(),
+            _ => return None,
+        };
+        Some(value)
+    });
+    text.trim().to_string()
+}
+
+/// Replaces each `{placeholder}` in the template with its value in a single
+/// pass, so a placeholder that's part of a value is left as it is.
+/// Placeholders without a value are kept
+pub fn substitute(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest
+            .find('}')
+            .and_then(|end| Some((end, value(&rest[1..end])?)));
+        match placeholder {
+            Some((end, value)) => {
+                text.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Shortens a post to fit within a limit. The url is never cut, the text
+/// after it is shortened first and then the text before it
+pub fn truncate(text: &str, url: &str, limit: TextLimit) -> String {
+    let (before, url, after) = match text.find(url) {
+        Some(start) => (&text[..start], url, &text[start + url.len()..]),
+        None => (text, "", ""),
+    };
+    let url_length = match url {
+        "" => 0,
+        url => limit.url_length.unwrap_or_else(|| url.chars().count()),
+    };
+
+    let budget = limit.max_length.saturating_sub(url_length);
+    let before_length = before.chars().count();
+    let after_length = after.chars().count();
+    if before_length + after_length <= budget {
+        return text.to_string();
+    }
+
+    if before_length <= budget {
+        let after = shorten(after, budget - before_length);
+        format!("{}{}{}", before, url, after)
+    } else if url.is_empty() {
+        shorten(before, budget)
+    } else {
+        // Leaves room for a space so the url doesn't run into the ellipsis
+        let before = shorten(before, budget.saturating_sub(1));
+        format!("{} {}", before, url).trim_start().to_string()
+    }
+}
+
+fn shorten(text: &str, length: usize) -> String {
+    if text.chars().count() <= length {
+        return text.to_string();
+    }
+    if length == 0 {
+        return String::new();
+    }
+
+    let shortened: String = text.chars().take(length - 1).collect();
+    let shortened = shortened.trim_end();
+    if shortened.is_empty() {
+        String::new()
+    } else {
+        format!("{}{}", shortened, ELLIPSIS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::track_details;
+
+    const URL: &str = "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC";
+
+    fn limit(max_length: usize, url_length: Option<usize>) -> TextLimit {
+        TextLimit {
+            max_length,
+            url_length,
+        }
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        let text = render(
+            "{name} by {artists} from {album} in {playlist} #{playlist_tag} {url}",
+            &track_details(),
+        );
+
+        assert_eq!(
+            text,
+            format!(
+                "Time (You and I) by Khruangbin from Mordechai in Coffee in the Morning \
+                #CoffeeInTheMorning {}",
+                URL
+            )
+        );
+    }
+
+    #[test]
+    fn renders_missing_values_as_empty() {
+        let mut track = track_details();
+        track.album = None;
+        track.playlists.clear();
+
+        assert_eq!(render("{url} {album}{playlist}", &track), URL);
+    }
+
+    #[test]
+    fn leaves_placeholders_in_values_as_they_are() {
+        let mut track = track_details();
+        track.track.name = "Play {url} {album}".to_string();
+
+        assert_eq!(
+            render("{name} - {unknown}", &track),
+            "Play {url} {album} - {unknown}"
+        );
+    }
+
+    #[test]
+    fn leaves_text_within_the_limit_alone() {
+        let text = format!("Time (You and I) {}", URL);
+
+        assert_eq!(truncate(&text, URL, limit(280, None)), text);
+    }
+
+    #[test]
+    fn shortens_the_text_after_the_url_first() {
+        let text = format!("Time {} by Khruangbin", URL);
+
+        // The url counts as 23 characters, leaving room for "Time " and 4 more
+        let truncated = truncate(&text, URL, limit(32, Some(23)));
+
+        assert_eq!(truncated, format!("Time {} by…", URL));
+    }
+
+    #[test]
+    fn never_cuts_the_url() {
+        let text = format!("Time (You and I) by Khruangbin {}", URL);
+
+        let truncated = truncate(&text, URL, limit(30, Some(23)));
+        assert_eq!(truncated, format!("Time… {}", URL));
+
+        // Even when the url alone is over the limit
+        let truncated = truncate(&text, URL, limit(20, None));
+        assert_eq!(truncated, URL);
+    }
+
+    #[test]
+    fn counts_characters_rather_than_bytes() {
+        let text = format!("Café Tacvba — Las Flores {}", URL);
+
+        // 25 characters before the url, but 28 bytes
+        let truncated = truncate(&text, URL, limit(25 + URL.len(), None));
+        assert_eq!(truncated, text);
+
+        let truncated = truncate(&text, URL, limit(14 + URL.len(), None));
+        assert_eq!(truncated, format!("Café Tacvba… {}", URL));
+    }
+
+    #[test]
+    fn shortens_text_without_a_url() {
+        let truncated = truncate("Time (You and I)", URL, limit(8, None));
+
+        assert_eq!(truncated, "Time (Y…");
+    }
+}
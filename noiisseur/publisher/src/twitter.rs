@@ -0,0 +1,615 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use ini::Ini;
+use oauth;
+use rand::RngCore;
+use reqwest::{
+    blocking::{Client, Response},
+    header, Url,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use database::models::TrackDetails;
+
+use crate::{error::Error, PublishReceipt, Publisher, TextLimit};
+
+const TWITTER_API_URL: &str = "https://api.twitter.com";
+const TWITTER_AUTH_URL: &str = "https://twitter.com/i/oauth2/authorize";
+const TWITTER_URL: &str = "https://twitter.com";
+const SCOPE: &str = "tweet.read tweet.write users.read offline.access";
+const REDIRECT_URI: &str = "http://localhost:8000/twitter/auth";
+const MAX_TWEET_LENGTH: usize = 280;
+// Every link is shortened with t.co, which always counts as 23 characters
+const TWEET_URL_LENGTH: usize = 23;
+
+#[derive(oauth::Request)]
+struct Tweet {
+    status: String,
+}
+
+#[derive(oauth::Request)]
+struct DestroyTweet {
+    trim_user: bool,
+}
+
+#[derive(Deserialize)]
+struct TweetUser {
+    screen_name: String,
+}
+
+#[derive(Deserialize)]
+struct TweetResponse {
+    id_str: String,
+    user: TweetUser,
+}
+
+#[derive(Deserialize)]
+struct TweetV2 {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct TweetV2Response {
+    data: TweetV2,
+}
+
+#[derive(Deserialize)]
+struct TwitterAuth {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+/// The Twitter API used to post tweets
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    /// The v1.1 API, signed with OAuth 1.0a using the `TWITTER_*` environment variables
+    #[default]
+    V1,
+    /// The v2 API, using an OAuth 2.0 user token from `noi auth twitter`
+    V2,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TwitterConfig {
+    pub api_version: ApiVersion,
+    /// The base url of the API. Only needs to be changed when testing
+    pub api_base_url: String,
+    /// The OAuth 2.0 client id of the app, required for v2
+    pub client_id: Option<String>,
+    /// The OAuth 2.0 client secret, only set for confidential clients
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+}
+
+impl Default for TwitterConfig {
+    fn default() -> Self {
+        TwitterConfig {
+            api_version: ApiVersion::default(),
+            api_base_url: TWITTER_API_URL.to_string(),
+            client_id: None,
+            client_secret: None,
+            redirect_uri: REDIRECT_URI.to_string(),
+        }
+    }
+}
+
+impl TwitterConfig {
+    fn api_url(&self, path: &str) -> String {
+        format!(
+            "{api_base_url}{path}",
+            api_base_url = self.api_base_url.trim_end_matches('/'),
+            path = path
+        )
+    }
+
+    fn client_id(&self) -> Result<&str> {
+        let client_id = self
+            .client_id
+            .as_deref()
+            .with_context(|| "The Twitter v2 API requires a client_id in the config")?;
+        Ok(client_id)
+    }
+}
+
+/// What tweets are posted with, which depends on the API version
+pub enum Credentials {
+    /// The OAuth 1.0a token that signs v1.1 requests
+    V1(oauth::Token),
+    /// The file the OAuth 2.0 user token from `noi auth twitter` is saved in.
+    /// The token is refreshed and saved again on every v2 request
+    V2(PathBuf),
+}
+
+impl Credentials {
+    /// Reads the token from the `TWITTER_*` environment variables for v1.1, or
+    /// uses `~/.twitter/credentials` for v2
+    pub fn load(config: &TwitterConfig) -> Result<Self> {
+        let credentials = match config.api_version {
+            ApiVersion::V1 => Credentials::V1(token_from_env()?),
+            ApiVersion::V2 => Credentials::V2(credentials_file()?),
+        };
+        Ok(credentials)
+    }
+}
+
+/// Posts tracks as tweets using either the v1.1 or v2 Twitter API
+pub struct Twitter {
+    config: TwitterConfig,
+    credentials: Credentials,
+    client: Client,
+}
+
+impl Twitter {
+    pub fn new(config: TwitterConfig) -> Result<Self> {
+        let credentials = Credentials::load(&config)?;
+        Ok(Twitter::with_credentials(config, credentials))
+    }
+
+    pub fn with_credentials(config: TwitterConfig, credentials: Credentials) -> Self {
+        Twitter {
+            config,
+            credentials,
+            client: Client::new(),
+        }
+    }
+
+    fn check(&self, response: Response) -> Result<Response> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        Err(Error::Rejected {
+            destination: self.name().to_string(),
+            status: response.status().as_u16(),
+            body: response.text()?,
+        }
+        .into())
+    }
+
+    fn publish_v1(&self, token: &oauth::Token, text: &str) -> Result<PublishReceipt> {
+        let tweet_url = self.config.api_url("/1.1/statuses/update.json");
+        let request = Tweet {
+            status: text.to_string(),
+        };
+        // Creates the authentication header
+        let auth_header = oauth::post(&tweet_url, &request, token, oauth::HmacSha1);
+
+        let mut params = HashMap::new();
+        params.insert("status", text);
+        let response = self
+            .client
+            .post(&tweet_url)
+            .header(header::AUTHORIZATION, auth_header)
+            .form(&params)
+            .send()?;
+
+        let tweet: TweetResponse = self.check(response)?.json()?;
+        let url = format!(
+            "{twitter_url}/{user}/status/{id}",
+            twitter_url = TWITTER_URL,
+            user = tweet.user.screen_name,
+            id = tweet.id_str
+        );
+
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: Some(tweet.id_str),
+            remote_url: Some(url),
+        })
+    }
+
+    fn publish_v2(&self, path: &Path, text: &str) -> Result<PublishReceipt> {
+        let access_token = refresh_access_token(&self.config, path)?;
+
+        let response = self
+            .client
+            .post(&self.config.api_url("/2/tweets"))
+            .bearer_auth(access_token)
+            .json(&json!({ "text": text }))
+            .send()?;
+
+        let tweet: TweetV2Response = self.check(response)?.json()?;
+        // The v2 response doesn't include the author, this url resolves without it
+        let url = format!(
+            "{twitter_url}/i/web/status/{id}",
+            twitter_url = TWITTER_URL,
+            id = tweet.data.id
+        );
+
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: Some(tweet.data.id),
+            remote_url: Some(url),
+        })
+    }
+
+    fn unpublish_v1(&self, token: &oauth::Token, remote_id: &str) -> Result<()> {
+        let destroy_url = self
+            .config
+            .api_url(&format!("/1.1/statuses/destroy/{}.json", remote_id));
+        let request = DestroyTweet { trim_user: true };
+        let auth_header = oauth::post(&destroy_url, &request, token, oauth::HmacSha1);
+
+        let response = self
+            .client
+            .post(&destroy_url)
+            .header(header::AUTHORIZATION, auth_header)
+            .form(&[("trim_user", "true")])
+            .send()?;
+
+        self.check(response)?;
+        Ok(())
+    }
+
+    fn unpublish_v2(&self, path: &Path, remote_id: &str) -> Result<()> {
+        let access_token = refresh_access_token(&self.config, path)?;
+
+        let response = self
+            .client
+            .delete(&self.config.api_url(&format!("/2/tweets/{}", remote_id)))
+            .bearer_auth(access_token)
+            .send()?;
+
+        self.check(response)?;
+        Ok(())
+    }
+}
+
+impl Publisher for Twitter {
+    fn name(&self) -> &str {
+        "twitter"
+    }
+
+    fn publish(&self, _track: &TrackDetails, text: &str) -> Result<PublishReceipt> {
+        match &self.credentials {
+            Credentials::V1(token) => self.publish_v1(token, text),
+            Credentials::V2(path) => self.publish_v2(path, text),
+        }
+    }
+
+    fn text_limit(&self) -> Option<TextLimit> {
+        Some(TextLimit {
+            max_length: MAX_TWEET_LENGTH,
+            url_length: Some(TWEET_URL_LENGTH),
+        })
+    }
+
+    fn unpublish(&self, remote_id: &str) -> Result<()> {
+        match &self.credentials {
+            Credentials::V1(token) => self.unpublish_v1(token, remote_id),
+            Credentials::V2(path) => self.unpublish_v2(path, remote_id),
+        }
+    }
+}
+
+/// Reads the OAuth 1.0a token from the `TWITTER_*` environment variables
+fn token_from_env() -> Result<oauth::Token> {
+    let var = |name: &'static str| env::var(name).map_err(|_| Error::MissingVariable(name));
+
+    let token = oauth::Token::from_parts(
+        var("TWITTER_CONSUMER_KEY")?,
+        var("TWITTER_CONSUMER_SECRET")?,
+        var("TWITTER_ACCESS_TOKEN")?,
+        var("TWITTER_ACCESS_TOKEN_SECRET")?,
+    );
+    Ok(token)
+}
+
+fn credentials_file() -> Result<PathBuf> {
+    let mut save_path = home_dir()
+        .with_context(|| "Could not find the home directory the Twitter credentials are kept in")?;
+    save_path.push(".twitter");
+    save_path.push("credentials");
+    Ok(save_path)
+}
+
+fn save_credentials(path: &Path, auth: &TwitterAuth) -> Result<()> {
+    let mut credentials = Ini::load_from_file(path).unwrap_or_else(|_| Ini::new());
+
+    credentials
+        .with_section(Some("default"))
+        .set("access_token", auth.access_token.as_str());
+    // Twitter rotates refresh tokens, each one can only be used once
+    if let Some(refresh_token) = &auth.refresh_token {
+        credentials
+            .with_section(Some("default"))
+            .set("refresh_token", refresh_token.as_str());
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    credentials.write_to_file(path)?;
+    Ok(())
+}
+
+/// Exchanges a grant for a user token and saves it to `path`
+fn request_token(
+    config: &TwitterConfig,
+    path: &Path,
+    params: &[(&str, &str)],
+) -> Result<TwitterAuth> {
+    let client_id = config.client_id()?;
+    let mut body = form_urlencoded::Serializer::new(String::new());
+    for (name, value) in params.iter() {
+        body.append_pair(name, value);
+    }
+    body.append_pair("client_id", client_id);
+    let body = body.finish();
+
+    let client = Client::new();
+    let mut request = client
+        .post(&config.api_url("/2/oauth2/token"))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(body);
+    // Confidential clients authenticate with their secret as well
+    if let Some(client_secret) = &config.client_secret {
+        request = request.basic_auth(client_id, Some(client_secret));
+    }
+
+    let response = request.send()?;
+    if !response.status().is_success() {
+        return Err(Error::Rejected {
+            destination: "twitter".to_string(),
+            status: response.status().as_u16(),
+            body: response.text()?,
+        }
+        .into());
+    }
+
+    let auth: TwitterAuth = response.json()?;
+    save_credentials(path, &auth)?;
+    Ok(auth)
+}
+
+/// Uses the refresh token saved in `path` to get a new user access token
+pub fn refresh_access_token(config: &TwitterConfig, path: &Path) -> Result<String> {
+    let credentials = Ini::load_from_file(path)
+        .with_context(|| "No Twitter credentials found, run `noi auth twitter` first")?;
+    let refresh_token = credentials
+        .get_from(Some("default"), "refresh_token")
+        .with_context(|| "No Twitter refresh token found, run `noi auth twitter` first")?
+        .to_string();
+
+    let auth = request_token(
+        config,
+        path,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &refresh_token),
+        ],
+    )?;
+    Ok(auth.access_token)
+}
+
+/// Runs the OAuth 2.0 authorization code flow with PKCE to get a user token
+/// for the v2 API
+pub fn authenticate(config: &TwitterConfig) -> Result<()> {
+    let client_id = config.client_id()?;
+
+    let random_string = || {
+        let mut bytes = [0; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    };
+    let state = random_string();
+    let code_verifier = random_string();
+    let code_challenge = base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    );
+
+    let url = {
+        let params = form_urlencoded::Serializer::new(String::new())
+            .append_pair("response_type", "code")
+            .append_pair("client_id", client_id)
+            .append_pair("redirect_uri", &config.redirect_uri)
+            .append_pair("scope", SCOPE)
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256")
+            .finish();
+
+        format!(
+            "{auth_url}?{params}",
+            auth_url = TWITTER_AUTH_URL,
+            params = params
+        )
+    };
+
+    println!("Opening {}", url);
+    webbrowser::open(&url)?;
+
+    // Nothing listens on the redirect uri, the code is read from the url the
+    // browser ends up on instead
+    print!("Paste the url you were redirected to: ");
+    io::stdout().flush()?;
+    let mut redirect = String::new();
+    io::stdin().read_line(&mut redirect)?;
+
+    let redirect = Url::parse(redirect.trim())?;
+    let query: HashMap<_, _> = redirect.query_pairs().into_owned().collect();
+    if query.get("state") != Some(&state) {
+        return Err(Error::InvalidOAuthState.into());
+    }
+    let code = query
+        .get("code")
+        .with_context(|| "The redirect url has no authorization code")?;
+
+    request_token(
+        config,
+        &credentials_file()?,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &config.redirect_uri),
+            ("code_verifier", &code_verifier),
+        ],
+    )?;
+
+    println!("Successfully authenticated!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{mock, Matcher};
+
+    use super::*;
+    use crate::tests::track_details;
+
+    fn twitter_v1() -> Twitter {
+        let key = || "test".to_string();
+        let token = oauth::Token::from_parts(key(), key(), key(), key());
+        Twitter::with_credentials(
+            TwitterConfig {
+                api_base_url: mockito::server_url(),
+                ..TwitterConfig::default()
+            },
+            Credentials::V1(token),
+        )
+    }
+
+    #[test]
+    fn publishes_a_v1_tweet() {
+        let update = mock("POST", "/1.1/statuses/update.json")
+            .match_body(Matcher::UrlEncoded(
+                "status".to_string(),
+                "Time (You and I)".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id_str": "1234", "user": {"screen_name": "noiisseur"}}"#)
+            .create();
+
+        let receipt = twitter_v1()
+            .publish(&track_details(), "Time (You and I)")
+            .unwrap();
+
+        update.assert();
+        assert_eq!(receipt.destination, "twitter");
+        assert_eq!(receipt.remote_id.as_deref(), Some("1234"));
+        assert_eq!(
+            receipt.remote_url.as_deref(),
+            Some("https://twitter.com/noiisseur/status/1234")
+        );
+    }
+
+    #[test]
+    fn reports_a_rejected_v1_tweet() {
+        let update = mock("POST", "/1.1/statuses/update.json")
+            .with_status(403)
+            .with_body("duplicate status")
+            .create();
+
+        let error = twitter_v1()
+            .publish(&track_details(), "Time (You and I)")
+            .unwrap_err();
+
+        update.assert();
+        match error.downcast_ref::<Error>() {
+            Some(Error::Rejected { status, body, .. }) => {
+                assert_eq!(*status, 403);
+                assert_eq!(body, "duplicate status");
+            }
+            _ => panic!("expected the tweet to be rejected, got {}", error),
+        }
+    }
+
+    #[test]
+    fn deletes_a_v1_tweet() {
+        let destroy = mock("POST", "/1.1/statuses/destroy/1234.json")
+            .match_body(Matcher::UrlEncoded(
+                "trim_user".to_string(),
+                "true".to_string(),
+            ))
+            .with_body(r#"{"id_str": "1234"}"#)
+            .create();
+
+        twitter_v1().unpublish("1234").unwrap();
+
+        destroy.assert();
+    }
+
+    #[test]
+    fn publishes_and_deletes_a_v2_tweet() {
+        // Every v2 request refreshes the token saved in the credentials file
+        let path = env::temp_dir().join(format!("noi-twitter-{}", std::process::id()));
+        save_credentials(
+            &path,
+            &TwitterAuth {
+                access_token: "expired".to_string(),
+                refresh_token: Some("refresh-1".to_string()),
+            },
+        )
+        .unwrap();
+        let twitter = Twitter::with_credentials(
+            TwitterConfig {
+                api_version: ApiVersion::V2,
+                api_base_url: mockito::server_url(),
+                client_id: Some("noi".to_string()),
+                ..TwitterConfig::default()
+            },
+            Credentials::V2(path.clone()),
+        );
+
+        let refresh = mock("POST", "/2/oauth2/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("grant_type".to_string(), "refresh_token".to_string()),
+                Matcher::UrlEncoded("refresh_token".to_string(), "refresh-1".to_string()),
+                Matcher::UrlEncoded("client_id".to_string(), "noi".to_string()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"access_token": "access-2", "refresh_token": "refresh-2"}"#)
+            .create();
+        let tweet = mock("POST", "/2/tweets")
+            .match_header("authorization", "Bearer access-2")
+            .match_body(Matcher::Json(json!({ "text": "Time (You and I)" })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"data": {"id": "1234", "text": "Time (You and I)"}}"#)
+            .create();
+
+        let receipt = twitter
+            .publish(&track_details(), "Time (You and I)")
+            .unwrap();
+
+        refresh.assert();
+        tweet.assert();
+        assert_eq!(receipt.remote_id.as_deref(), Some("1234"));
+        assert_eq!(
+            receipt.remote_url.as_deref(),
+            Some("https://twitter.com/i/web/status/1234")
+        );
+
+        // Refresh tokens can only be used once, the new one must have been saved
+        let refresh = mock("POST", "/2/oauth2/token")
+            .match_body(Matcher::UrlEncoded(
+                "refresh_token".to_string(),
+                "refresh-2".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"access_token": "access-3", "refresh_token": "refresh-3"}"#)
+            .create();
+        let delete = mock("DELETE", "/2/tweets/1234")
+            .match_header("authorization", "Bearer access-3")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"data": {"deleted": true}}"#)
+            .create();
+
+        twitter.unpublish("1234").unwrap();
+
+        refresh.assert();
+        delete.assert();
+        fs::remove_file(path).unwrap();
+    }
+}
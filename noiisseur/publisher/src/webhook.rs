@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use reqwest::blocking::{Client, Response};
+use serde::Deserialize;
+use serde_json::json;
+
+use database::models::TrackDetails;
+
+use crate::{error::Error, template, PublishReceipt, Publisher, TextLimit};
+
+const DEFAULT_BODY_TEMPLATE: &str = r#"{"name": "{name}", "url": "{url}"}"#;
+const MAX_DISCORD_DESCRIPTION_LENGTH: usize = 4096;
+const MAX_SLACK_TEXT_LENGTH: usize = 3000;
+
+fn default_body_template() -> String {
+    DEFAULT_BODY_TEMPLATE.to_string()
+}
+
+fn check(destination: &str, response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    Err(Error::Rejected {
+        destination: destination.to_string(),
+        status: response.status().as_u16(),
+        body: response.text()?,
+    }
+    .into())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordConfig {
+    pub webhook_url: String,
+}
+
+#[derive(Deserialize)]
+struct DiscordMessage {
+    id: String,
+}
+
+/// Posts tracks to a Discord channel as an embed
+pub struct Discord {
+    config: DiscordConfig,
+    client: Client,
+}
+
+impl Discord {
+    pub fn new(config: DiscordConfig) -> Self {
+        Discord {
+            config,
+            client: Client::new(),
+        }
+    }
+}
+
+impl Publisher for Discord {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    fn publish(&self, track: &TrackDetails, text: &str) -> Result<PublishReceipt> {
+        let mut embed = json!({
+            "title": track.track.name,
+            "url": track.track.url,
+            "description": text,
+        });
+        if !track.artists.is_empty() {
+            embed["fields"] = json!([{ "name": "Artist", "value": track.artist_names() }]);
+        }
+        if let Some(thumbnail_url) = track.album.as_ref().and_then(|a| a.thumbnail_url.as_ref()) {
+            embed["thumbnail"] = json!({ "url": thumbnail_url });
+        }
+        let body = json!({ "embeds": [embed] });
+
+        // Without `wait` Discord replies with an empty 204 and we don't get the message back
+        let response = self
+            .client
+            .post(&self.config.webhook_url)
+            .query(&[("wait", "true")])
+            .json(&body)
+            .send()?;
+
+        let message: DiscordMessage = check(self.name(), response)?.json()?;
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: Some(message.id),
+            remote_url: None,
+        })
+    }
+
+    fn text_limit(&self) -> Option<TextLimit> {
+        Some(TextLimit {
+            max_length: MAX_DISCORD_DESCRIPTION_LENGTH,
+            url_length: None,
+        })
+    }
+
+    fn unpublish(&self, remote_id: &str) -> Result<()> {
+        let message_url = format!(
+            "{webhook_url}/messages/{id}",
+            webhook_url = self.config.webhook_url.trim_end_matches('/'),
+            id = remote_id
+        );
+
+        let response = self.client.delete(&message_url).send()?;
+        check(self.name(), response)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackConfig {
+    pub webhook_url: String,
+}
+
+/// Posts tracks to a Slack channel using Block Kit
+pub struct Slack {
+    config: SlackConfig,
+    client: Client,
+}
+
+impl Slack {
+    pub fn new(config: SlackConfig) -> Self {
+        Slack {
+            config,
+            client: Client::new(),
+        }
+    }
+}
+
+impl Publisher for Slack {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn publish(&self, _track: &TrackDetails, text: &str) -> Result<PublishReceipt> {
+        // Control characters have to be escaped in mrkdwn, links are still detected
+        let mrkdwn = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let body = json!({
+            // Used in notifications where blocks aren't rendered
+            "text": text,
+            "blocks": [{
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": mrkdwn },
+            }],
+        });
+
+        let response = self
+            .client
+            .post(&self.config.webhook_url)
+            .json(&body)
+            .send()?;
+        let response = check(self.name(), response)?;
+
+        // Slack replies with a plain `ok` when the message was accepted
+        let status = response.status().as_u16();
+        let text = response.text()?;
+        if text.trim() != "ok" {
+            return Err(Error::Rejected {
+                destination: self.name().to_string(),
+                status,
+                body: text,
+            }
+            .into());
+        }
+
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: None,
+            remote_url: None,
+        })
+    }
+
+    fn text_limit(&self) -> Option<TextLimit> {
+        Some(TextLimit {
+            max_length: MAX_SLACK_TEXT_LENGTH,
+            url_length: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// The JSON body to send. `{name}`, `{url}`, `{spotify_id}`, `{artists}` and
+    /// `{album}` are replaced with the track's values and `{text}` with the
+    /// rendered post
+    #[serde(default = "default_body_template")]
+    pub body_template: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Posts tracks as JSON to an arbitrary endpoint
+pub struct Webhook {
+    config: WebhookConfig,
+    client: Client,
+}
+
+impl Webhook {
+    pub fn new(config: WebhookConfig) -> Self {
+        Webhook {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    fn render_body(&self, track: &TrackDetails, text: &str) -> Result<serde_json::Value> {
+        // Values are escaped so they can't break out of the JSON strings they're placed in
+        let escape = |value: &str| {
+            let quoted = serde_json::Value::String(value.to_string()).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        };
+
+        let album = track.album.as_ref().map(|a| a.name.as_str());
+        let body = template::substitute(&self.config.body_template, |placeholder| {
+            let value = match placeholder {
+                "name" => track.track.name.clone(),
+                "url" => track.track.url.clone(),
+                "spotify_id" => track.track.spotify_id.clone(),
+                "artists" => track.artist_names(),
+                "album" => album.unwrap_or_default().to_string(),
+                "text" => text.to_string(),
+                _ => return None,
+            };
+            Some(escape(&value))
+        });
+        let body = serde_json::from_str(&body)?;
+        Ok(body)
+    }
+}
+
+impl Publisher for Webhook {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn publish(&self, track: &TrackDetails, text: &str) -> Result<PublishReceipt> {
+        let body = self.render_body(track, text)?;
+
+        let mut request = self.client.post(&self.config.url).json(&body);
+        for (name, value) in self.config.headers.iter() {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        check(self.name(), request.send()?)?;
+
+        Ok(PublishReceipt {
+            destination: self.name().to_string(),
+            remote_id: None,
+            remote_url: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{mock, Matcher};
+
+    use super::*;
+    use crate::tests::track_details;
+
+    #[test]
+    fn publishes_a_discord_embed() {
+        let message = mock("POST", "/webhooks/1/token")
+            .match_query(Matcher::UrlEncoded("wait".to_string(), "true".to_string()))
+            .match_body(Matcher::Json(json!({
+                "embeds": [{
+                    "title": "Time (You and I)",
+                    "url": "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+                    "description": "Now playing",
+                    "fields": [{ "name": "Artist", "value": "Khruangbin" }],
+                    "thumbnail": { "url": "https://i.scdn.co/image/thumbnail" },
+                }],
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": "9001"}"#)
+            .create();
+        let discord = Discord::new(DiscordConfig {
+            webhook_url: format!("{}/webhooks/1/token", mockito::server_url()),
+        });
+
+        let receipt = discord.publish(&track_details(), "Now playing").unwrap();
+
+        message.assert();
+        assert_eq!(receipt.destination, "discord");
+        assert_eq!(receipt.remote_id.as_deref(), Some("9001"));
+        assert_eq!(receipt.remote_url, None);
+    }
+
+    #[test]
+    fn deletes_a_discord_message() {
+        let delete = mock("DELETE", "/webhooks/1/token/messages/9001")
+            .with_status(204)
+            .create();
+        let discord = Discord::new(DiscordConfig {
+            webhook_url: format!("{}/webhooks/1/token/", mockito::server_url()),
+        });
+
+        discord.unpublish("9001").unwrap();
+
+        delete.assert();
+    }
+
+    #[test]
+    fn publishes_escaped_slack_blocks() {
+        let message = mock("POST", "/services/slack")
+            .match_body(Matcher::Json(json!({
+                "text": "R&B <3",
+                "blocks": [{
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": "R&amp;B &lt;3" },
+                }],
+            })))
+            .with_body("ok")
+            .create();
+        let slack = Slack::new(SlackConfig {
+            webhook_url: format!("{}/services/slack", mockito::server_url()),
+        });
+
+        let receipt = slack.publish(&track_details(), "R&B <3").unwrap();
+
+        message.assert();
+        assert_eq!(receipt.destination, "slack");
+        assert_eq!(receipt.remote_id, None);
+    }
+
+    #[test]
+    fn reports_a_slack_message_that_was_not_ok() {
+        let message = mock("POST", "/services/slack-invalid")
+            .with_body("invalid_blocks")
+            .create();
+        let slack = Slack::new(SlackConfig {
+            webhook_url: format!("{}/services/slack-invalid", mockito::server_url()),
+        });
+
+        let error = slack.publish(&track_details(), "Now playing").unwrap_err();
+
+        message.assert();
+        match error.downcast_ref::<Error>() {
+            Some(Error::Rejected { body, .. }) => assert_eq!(body, "invalid_blocks"),
+            _ => panic!("expected the message to be rejected, got {}", error),
+        }
+        assert!(slack.unpublish("1").is_err());
+    }
+
+    #[test]
+    fn publishes_an_escaped_webhook_body_with_headers() {
+        let hook = mock("POST", "/hooks/noiisseur")
+            .match_header("authorization", "Bearer secret")
+            .match_body(Matcher::Json(json!({
+                "text": "Say \"hi\"\n",
+                "track": "Time (You and I)",
+                "album": "Mordechai",
+                "spotify_id": "4uLU6hMCjMI75M1A2tKUQC",
+            })))
+            .create();
+        let mut headers = HashMap::new();
+        headers.insert("Authorization".to_string(), "Bearer secret".to_string());
+        let webhook = Webhook::new(WebhookConfig {
+            url: format!("{}/hooks/noiisseur", mockito::server_url()),
+            body_template: concat!(
+                r#"{"text": "{text}", "track": "{name}", "#,
+                r#""album": "{album}", "spotify_id": "{spotify_id}"}"#
+            )
+            .to_string(),
+            headers,
+        });
+
+        let receipt = webhook.publish(&track_details(), "Say \"hi\"\n").unwrap();
+
+        hook.assert();
+        assert_eq!(receipt.destination, "webhook");
+    }
+
+    #[test]
+    fn renders_values_containing_quotes_and_placeholders_as_they_are() {
+        let mut track = track_details();
+        track.track.name = r#"Say "{url}""#.to_string();
+        let webhook = Webhook::new(WebhookConfig {
+            url: mockito::server_url(),
+            body_template: r#"{"name": "{name}", "text": "{text}", "url": "{url}"}"#.to_string(),
+            headers: HashMap::new(),
+        });
+
+        let body = webhook.render_body(&track, "{name}").unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "name": r#"Say "{url}""#,
+                "text": "{name}",
+                "url": "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+            })
+        );
+    }
+}
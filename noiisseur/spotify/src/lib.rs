@@ -5,10 +5,8 @@ pub mod error;
 use std::{env, path::PathBuf};
 
 use anyhow::Result;
-use base64;
 use csrf::{CsrfProtection, HmacCsrfProtection};
 use dirs::home_dir;
-use form_urlencoded;
 use hmac::{Hmac, Mac, NewMac};
 use ini::Ini;
 use lazy_static::{__Deref, lazy_static};
@@ -17,12 +15,9 @@ use ring::rand::{self, SecureRandom};
 use rocket::response::content::Html;
 use rocket::*;
 use serde::Deserialize;
-use serde_json;
 use sha2::Sha256;
-use webbrowser;
 
 // Constants
-const SPOTIFY_BASE_URL: &str = "https://api.spotify.com/v1";
 const SPOTIFY_AUTH_URL: &str = "https://accounts.spotify.com/authorize";
 const SPOTIFY_TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
 const RESPONSE_TYPE: &str = "code";
@@ -64,6 +59,37 @@ struct ExternalUrl {
     url: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExternalIds {
+    pub isrc: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpotifyArtist {
+    // Local files have no Spotify id
+    #[serde(rename = "id")]
+    pub spotify_id: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpotifyImage {
+    pub url: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpotifyAlbum {
+    #[serde(rename = "id")]
+    pub spotify_id: Option<String>,
+    pub name: String,
+    pub release_date: Option<String>,
+    /// Album art, ordered from largest to smallest
+    #[serde(default)]
+    pub images: Vec<SpotifyImage>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SpotifyTrackInner {
     #[serde(rename = "id")]
@@ -71,19 +97,43 @@ pub struct SpotifyTrackInner {
     pub name: String,
     #[serde(rename = "external_urls")]
     url: ExternalUrl,
+    pub duration_ms: Option<i32>,
+    pub explicit: Option<bool>,
+    pub popularity: Option<i32>,
+    pub external_ids: Option<ExternalIds>,
+    pub album: Option<SpotifyAlbum>,
+    #[serde(default)]
+    pub artists: Vec<SpotifyArtist>,
 }
+
+impl SpotifyTrackInner {
+    pub fn url(&self) -> &str {
+        &self.url.url
+    }
+
+    pub fn isrc(&self) -> Option<&str> {
+        self.external_ids.as_ref()?.isrc.as_deref()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpotifyUser {
+    pub id: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SpotifyTrack {
     #[serde(rename = "track")]
     pub track: Option<SpotifyTrackInner>,
+    /// When the track was added to the playlist e.g. `2021-03-27T08:19:04Z`.
+    /// Very old playlists don't have this
+    pub added_at: Option<String>,
+    pub added_by: Option<SpotifyUser>,
 }
 
 impl SpotifyTrack {
     pub fn is_null(&self) -> bool {
-        match self.track {
-            Some(_) => return false,
-            None => return true,
-        };
+        self.track.is_none()
     }
 
     pub fn spotify_id(&self) -> Option<String> {
@@ -91,7 +141,7 @@ impl SpotifyTrack {
             return None;
         };
 
-        let ref track = self.track.as_ref().unwrap();
+        let track = self.track.as_ref().unwrap();
         let spotify_id = track.spotify_id.clone();
         Some(spotify_id)
     }
@@ -116,6 +166,11 @@ impl SpotifyTrack {
         let url = track.url.url.clone();
         Some(url)
     }
+
+    /// The track along with its album and artists
+    pub fn details(&self) -> Option<&SpotifyTrackInner> {
+        self.track.as_ref()
+    }
 }
 
 // A Spotify track page is on object representing a paginated
@@ -127,6 +182,11 @@ struct SpotifyTrackPage {
     next: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct SpotifyPlaylistSnapshot {
+    snapshot_id: String,
+}
+
 #[derive(Deserialize)]
 struct SpotifyAccessAuth<'a> {
     pub access_token: &'a str,
@@ -183,13 +243,31 @@ pub fn refresh_access_token() -> Result<String> {
     Ok(refresh_auth.access_token.to_string())
 }
 
-pub fn get_tracks(access_token: &str, playlist_id: &str, offset: i32) -> Result<Vec<SpotifyTrack>> {
+/// Gets the snapshot id of a playlist. It changes whenever the playlist is
+/// changed, e.g. tracks are added, removed or reordered
+pub fn get_snapshot_id(access_token: &str, playlist_id: &str) -> Result<String> {
+    let playlist_url = format!(
+        "https://api.spotify.com/v1/playlists/{playlist_id}?fields=snapshot_id",
+        playlist_id = playlist_id
+    );
+
+    let response = Client::new()
+        .get(&playlist_url)
+        .bearer_auth(access_token)
+        .send()?
+        .text()?;
+    let snapshot: SpotifyPlaylistSnapshot = serde_json::from_str(&response)?;
+
+    Ok(snapshot.snapshot_id)
+}
+
+/// Gets every track in a playlist, in the playlist's order
+pub fn get_tracks(access_token: &str, playlist_id: &str) -> Result<Vec<SpotifyTrack>> {
     let mut tracks_url = format!(
         "https://api.spotify.com/v1/playlists/{playlist_id}/\
-        tracks?fields=next,items(track(id,name,external_urls))\
-        &offset={offset}",
-        playlist_id = playlist_id,
-        offset = offset
+        tracks?fields=next,items(added_at,added_by.id,track(id,name,external_urls,duration_ms,\
+        explicit,popularity,external_ids(isrc),artists(id,name),album(id,name,release_date,images)))",
+        playlist_id = playlist_id
     );
 
     let client = Client::new();
@@ -211,7 +289,7 @@ pub fn get_tracks(access_token: &str, playlist_id: &str, offset: i32) -> Result<
         tracks.append(&mut response.tracks);
 
         tracks_url = match response.next {
-            Some(url) => String::from(url),
+            Some(url) => url,
             None => break,
         };
     }